@@ -0,0 +1,136 @@
+//! Measures searches by name on a synthetic store about the size of the standard library plus a
+//! few hundred crates. Run with `cargo bench`.
+
+extern crate oxidoc;
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use oxidoc::backend::{Backend, MemoryBackend};
+use oxidoc::conversion::DocType;
+use oxidoc::document::{CrateInfo, ModPath};
+use oxidoc::store::{Docset, Store, StoreLocation};
+
+const CRATES: usize = 300;
+const MODULES_PER_CRATE: usize = 10;
+const ITEMS_PER_MODULE: usize = 30;
+const STD_MODULES: usize = 200;
+
+const ITERATIONS: u32 = 20;
+
+const SYLLABLES: &'static [&'static str] = &[
+    "hash", "map", "vec", "str", "buf", "read", "write", "io", "net", "sock", "file", "path",
+    "iter", "into", "from", "parse", "err", "or", "key", "val", "node", "tree", "set", "list",
+    "sync", "mutex", "arc", "rc", "cell", "ref", "mut", "box", "fmt", "debug", "show", "time",
+];
+
+/// A deterministic pseudo-random number generator, so every run measures the same store.
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self, bound: usize) -> usize {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        ((self.0 >> 33) as usize) % bound
+    }
+
+    fn name(&mut self, capitalize: bool) -> String {
+        let count = 1 + self.next(3);
+        let mut name = String::new();
+        for i in 0..count {
+            let syllable = SYLLABLES[self.next(SYLLABLES.len())];
+            if capitalize {
+                name.push_str(&syllable[..1].to_uppercase());
+                name.push_str(&syllable[1..]);
+            } else {
+                if i > 0 {
+                    name.push('_');
+                }
+                name.push_str(syllable);
+            }
+        }
+        name
+    }
+}
+
+fn add_crate(store: &mut Store, rng: &mut Lcg, name: &str, modules: usize) {
+    let crate_info = CrateInfo {
+        name: name.to_string(),
+        version: "1.0.0".to_string(),
+        lib_path: None,
+    };
+
+    let mut docset = Docset::new();
+    for _ in 0..modules {
+        let module = format!("{}::{}", name, rng.name(false));
+        docset.insert(StoreLocation::new(module.clone(),
+                                         crate_info.clone(),
+                                         ModPath::from(module.clone()),
+                                         DocType::Module,
+                                         false));
+
+        for i in 0..ITEMS_PER_MODULE {
+            let (item, doc_type) = if i % 2 == 0 {
+                (rng.name(true), DocType::Struct)
+            } else {
+                (rng.name(false), DocType::Function)
+            };
+            let path = format!("{}::{}", module, item);
+            docset.insert(StoreLocation::new(item,
+                                             crate_info.clone(),
+                                             ModPath::from(path),
+                                             doc_type,
+                                             false));
+        }
+    }
+
+    store.add_docset(crate_info, docset);
+}
+
+fn measure<F: FnMut()>(name: &str, iterations: u32, mut f: F) {
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f();
+    }
+    let elapsed = start.elapsed();
+    let total_ms = elapsed.as_secs() as f64 * 1000.0 + elapsed.subsec_nanos() as f64 / 1_000_000.0;
+    println!("{:<40} {:>10.3} ms", name, total_ms / iterations as f64);
+}
+
+fn main() {
+    let backend: Arc<Backend> = Arc::new(MemoryBackend::new());
+    let mut store = Store::with_backend(backend.clone());
+    let mut rng = Lcg(42);
+
+    add_crate(&mut store, &mut rng, "std", STD_MODULES);
+    for i in 0..CRATES {
+        let name = format!("{}{}", rng.name(false), i);
+        add_crate(&mut store, &mut rng, &name, MODULES_PER_CRATE);
+    }
+
+    let documents: usize = store.all_locations().len();
+    println!("Synthetic store with {} documents\n", documents);
+
+    measure("save (builds the path index)", 1, || store.save().unwrap());
+    let store = Store::load_from(backend).unwrap();
+
+    let queries = ["HashMap", "Has", "hshmp", "hasmhap", "std::hash_map", "io::read::ReadBuf", "zz"];
+    for query in queries.iter() {
+        measure(&format!("lookup_name({:?})", query), ITERATIONS, || {
+            store.lookup_name(query);
+        });
+    }
+
+    measure("incremental typing of \"hashmap\"", ITERATIONS, || {
+        let query = "hashmap";
+        for len in 1..query.len() + 1 {
+            store.lookup_name(&query[..len]);
+        }
+    });
+
+    measure("complete(\"std::hash\")", ITERATIONS, || {
+        store.complete("std::hash", 50);
+    });
+    measure("complete(\"HashM\")", ITERATIONS, || {
+        store.complete("HashM", 50);
+    });
+}
@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, Mutex};
+
+use libc;
+
+use paths;
+use ::errors::*;
+
+/// Extension of temporary files which are renamed once completely written.
+pub const TEMP_EXTENSION: &str = "tmp";
+
+/// Storage for the store index and the documentation archives, which are kept as named blobs of
+/// bytes. The filesystem backend is used by the command line, while the in-memory backend lets
+/// library users and tests build and query stores without touching the home directory.
+pub trait Backend: fmt::Debug + Send + Sync {
+    /// Describes where `name` is kept, for use in messages.
+    fn describe(&self, name: &str) -> String;
+
+    fn exists(&self, name: &str) -> bool;
+
+    /// Whether `name` is a directory rather than a blob of bytes, like the per-document
+    /// directories of old versions of oxidoc.
+    fn is_dir(&self, name: &str) -> bool;
+
+    /// Reads all of `name`.
+    fn read(&self, name: &str) -> Result<Vec<u8>>;
+
+    /// Reads several `(offset, length)` ranges of `name`. All ranges are read from the same
+    /// contents of `name`, even if it's replaced at the same time. Ranges past the end are
+    /// returned shortened.
+    fn read_ranges(&self, name: &str, ranges: &[(u64, u64)]) -> Result<Vec<Vec<u8>>>;
+
+    /// Replaces the contents of `name`. Readers either see the old or the new contents.
+    fn write(&self, name: &str, data: &[u8]) -> Result<()>;
+
+    /// Removes `name`. Directories are only removed if they hold nothing but documentation
+    /// files.
+    fn remove(&self, name: &str) -> Result<()>;
+
+    /// Returns the names of everything in the backend.
+    fn list(&self) -> Result<Vec<String>>;
+}
+
+/// Returns the backend of the writable store.
+pub fn default_backend() -> Result<Arc<Backend>> {
+    Ok(Arc::new(FsBackend::new(paths::doc_registry_path()?)))
+}
+
+/// Keeps everything in files inside a store root directory.
+#[derive(Debug)]
+pub struct FsBackend {
+    root: PathBuf,
+}
+
+impl FsBackend {
+    pub fn new(root: PathBuf) -> Self {
+        FsBackend {
+            root: root,
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.root.join(name)
+    }
+}
+
+impl Backend for FsBackend {
+    fn describe(&self, name: &str) -> String {
+        self.path(name).display().to_string()
+    }
+
+    fn exists(&self, name: &str) -> bool {
+        self.path(name).exists()
+    }
+
+    fn is_dir(&self, name: &str) -> bool {
+        self.path(name).is_dir()
+    }
+
+    fn read(&self, name: &str) -> Result<Vec<u8>> {
+        let path = self.path(name);
+        let mut data = Vec::new();
+
+        File::open(&path)
+            .and_then(|mut file| file.read_to_end(&mut data))
+            .chain_err(|| format!("Failed to read file {}", path.display()))?;
+
+        Ok(data)
+    }
+
+    fn read_ranges(&self, name: &str, ranges: &[(u64, u64)]) -> Result<Vec<Vec<u8>>> {
+        let path = self.path(name);
+        let mut file = File::open(&path)
+            .chain_err(|| format!("Could not open file {}", path.display()))?;
+
+        let mut result = Vec::new();
+        for &(offset, length) in ranges.iter() {
+            let mut data = Vec::new();
+            file.seek(SeekFrom::Start(offset))
+                .and_then(|_| file.by_ref().take(length).read_to_end(&mut data))
+                .chain_err(|| format!("Failed to read file {}", path.display()))?;
+            result.push(data);
+        }
+
+        Ok(result)
+    }
+
+    fn write(&self, name: &str, data: &[u8]) -> Result<()> {
+        fs::create_dir_all(&self.root)
+            .chain_err(|| format!("Failed to create directory {}", self.root.display()))?;
+
+        write_atomically(&self.path(name), data)
+    }
+
+    fn remove(&self, name: &str) -> Result<()> {
+        let path = self.path(name);
+        if path.is_dir() {
+            return remove_doc_dir(&path);
+        }
+
+        fs::remove_file(&path).chain_err(|| format!("Could not remove {}", path.display()))
+    }
+
+    fn list(&self) -> Result<Vec<String>> {
+        if !self.root.exists() {
+            return Ok(Vec::new());
+        }
+
+        let entries = fs::read_dir(&self.root)
+            .chain_err(|| format!("Could not read directory {}", self.root.display()))?;
+
+        let mut names = Vec::new();
+        for entry in entries {
+            let entry = entry.chain_err(|| "Could not read directory entry")?;
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+
+        Ok(names)
+    }
+}
+
+/// Removes a directory holding nothing but documentation files and subdirectories of them. If
+/// anything else is found, nothing is removed and `ForeignFiles` is returned.
+fn remove_doc_dir(dir: &Path) -> Result<()> {
+    if !contains_only_doc_files(dir)? {
+        bail!(ErrorKind::ForeignFiles(dir.display().to_string()));
+    }
+
+    fs::remove_dir_all(dir).chain_err(|| format!("Could not remove {}", dir.display()))
+}
+
+fn contains_only_doc_files(dir: &Path) -> Result<bool> {
+    let entries = fs::read_dir(dir)
+        .chain_err(|| format!("Could not read directory {}", dir.display()))?;
+
+    for entry in entries {
+        let path = entry.chain_err(|| "Could not read directory entry")?.path();
+        let is_doc_file = path.extension().map_or(false, |ext| ext == paths::ARCHIVE_EXTENSION);
+
+        if path.is_dir() {
+            if !contains_only_doc_files(&path)? {
+                return Ok(false);
+            }
+        } else if !is_doc_file {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Whether `name` is a temporary file of `write_atomically` whose writer has exited without
+/// renaming it. Files of writers which are still running are never stale.
+pub fn is_stale_temp_file(name: &str) -> bool {
+    // .{file_name}.{pid}.tmp
+    let suffix = format!(".{}", TEMP_EXTENSION);
+    if !name.starts_with('.') || !name.ends_with(&suffix) {
+        return false;
+    }
+
+    let stem = &name[..name.len() - suffix.len()];
+    match stem.rsplit('.').next().and_then(|pid| pid.parse::<u32>().ok()) {
+        Some(pid) => !is_process_running(pid),
+        None      => false,
+    }
+}
+
+#[cfg(unix)]
+fn is_process_running(pid: u32) -> bool {
+    // Signal 0 only checks whether the process exists. It may belong to another user.
+    let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn is_process_running(_pid: u32) -> bool {
+    // TODO: Use OpenProcess on Windows. Until then temporary files are never removed.
+    true
+}
+
+/// Replaces the file at `path` with `data`. The data is written to a temporary file next to it
+/// first and renamed over it, so readers either see the old or the new file and a crash doesn't
+/// leave a partially written file behind.
+pub fn write_atomically(path: &Path, data: &[u8]) -> Result<()> {
+    let parent_path = path.parent().unwrap_or(Path::new("."));
+    let file_name = path.file_name().map_or("".into(), |n| n.to_string_lossy());
+    let temp_path = parent_path.join(format!(".{}.{}.{}", file_name, process::id(), TEMP_EXTENSION));
+
+    let result = File::create(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(data)?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp_path, path));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+
+    result.chain_err(|| format!("Failed to write file {}", path.display()))
+}
+
+/// Keeps everything in memory. Nothing is persisted when it's dropped.
+#[derive(Debug, Default)]
+pub struct MemoryBackend {
+    files: Mutex<HashMap<String, Arc<Vec<u8>>>>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        MemoryBackend::default()
+    }
+
+    fn get(&self, name: &str) -> Result<Arc<Vec<u8>>> {
+        match self.files.lock().unwrap().get(name) {
+            Some(data) => Ok(data.clone()),
+            None       => bail!("{} does not exist", self.describe(name)),
+        }
+    }
+}
+
+impl Backend for MemoryBackend {
+    fn describe(&self, name: &str) -> String {
+        format!("<memory>/{}", name)
+    }
+
+    fn exists(&self, name: &str) -> bool {
+        self.files.lock().unwrap().contains_key(name)
+    }
+
+    fn is_dir(&self, _name: &str) -> bool {
+        false
+    }
+
+    fn read(&self, name: &str) -> Result<Vec<u8>> {
+        Ok(self.get(name)?.as_ref().clone())
+    }
+
+    fn read_ranges(&self, name: &str, ranges: &[(u64, u64)]) -> Result<Vec<Vec<u8>>> {
+        let data = self.get(name)?;
+
+        let result = ranges.iter().map(|&(offset, length)| {
+            let start = (offset as usize).min(data.len());
+            let end = (offset.saturating_add(length) as usize).min(data.len());
+            data[start..end].to_vec()
+        }).collect();
+
+        Ok(result)
+    }
+
+    fn write(&self, name: &str, data: &[u8]) -> Result<()> {
+        self.files.lock().unwrap().insert(name.to_string(), Arc::new(data.to_vec()));
+        Ok(())
+    }
+
+    fn remove(&self, name: &str) -> Result<()> {
+        self.files.lock().unwrap().remove(name);
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<String>> {
+        Ok(self.files.lock().unwrap().keys().cloned().collect())
+    }
+}
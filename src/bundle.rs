@@ -0,0 +1,101 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+
+use backend;
+use document::CrateInfo;
+use store::{self, Docset};
+use text_search::DocsetTextIndex;
+use type_search::DocsetSignatures;
+use ::errors::*;
+
+/// Extension of bundle files written by `oxidoc export`.
+pub const BUNDLE_EXTENSION: &str = "oxdoc";
+
+/// Written at the start of every bundle. It's followed by the compressed bundle, which starts
+/// with the usual format header.
+const BUNDLE_MAGIC: &'static [u8] = b"OXIDOC-BUNDLE\n";
+
+/// The documentation of a single crate version, packed into one file so it can be generated once
+/// and imported into the stores of other machines. Bundles don't refer to any paths on the machine
+/// which created them.
+#[derive(Serialize, Deserialize)]
+pub struct Bundle {
+    /// The version of oxidoc which created the bundle.
+    pub generator: String,
+    pub crate_info: CrateInfo,
+    pub docset: Docset,
+    /// The documentation archive of the crate version, which the docset's locations point into.
+    pub archive: Vec<u8>,
+    /// The full-text index of the crate version, if it was indexed.
+    pub text_index: Option<DocsetTextIndex>,
+    /// The signatures of the functions and methods of the crate version, if they were indexed.
+    pub signatures: Option<DocsetSignatures>,
+}
+
+impl Bundle {
+    pub fn new(crate_info: CrateInfo,
+               docset: Docset,
+               archive: Vec<u8>,
+               text_index: Option<DocsetTextIndex>,
+               signatures: Option<DocsetSignatures>) -> Self {
+        Bundle {
+            generator: format!("oxidoc {}", env!("CARGO_PKG_VERSION")),
+            crate_info: crate_info,
+            docset: docset,
+            archive: archive,
+            text_index: text_index,
+            signatures: signatures,
+        }
+    }
+
+    /// Returns the name of the file the bundle is exported to by default.
+    pub fn default_file_name(&self) -> String {
+        format!("{}-{}.{}", self.crate_info.name, self.crate_info.version, BUNDLE_EXTENSION)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let encoded = store::encode_object(self)?;
+
+        let mut encoder = ZlibEncoder::new(BUNDLE_MAGIC.to_vec(), Compression::Default);
+        encoder.write_all(encoded.as_slice())
+            .chain_err(|| format!("Could not compress bundle for {}", self.crate_info))?;
+        encoder.finish()
+            .chain_err(|| format!("Could not compress bundle for {}", self.crate_info))
+    }
+
+    /// Reads a bundle written by `to_bytes`, read from `source`.
+    pub fn from_bytes(data: &[u8], source: &str) -> Result<Self> {
+        if !data.starts_with(BUNDLE_MAGIC) {
+            bail!("{} is not an oxidoc bundle", source);
+        }
+
+        let mut encoded = Vec::new();
+        ZlibDecoder::new(&data[BUNDLE_MAGIC.len()..])
+            .read_to_end(&mut encoded)
+            .chain_err(|| format!("Could not decompress bundle {}", source))?;
+
+        store::decode_object(&encoded, source)
+    }
+
+    pub fn save<T: AsRef<Path>>(&self, path: T) -> Result<()> {
+        let data = self.to_bytes()?;
+        backend::write_atomically(path.as_ref(), data.as_slice())
+    }
+
+    pub fn load<T: AsRef<Path>>(path: T) -> Result<Self> {
+        let path = path.as_ref();
+        let source = path.display().to_string();
+
+        let mut data = Vec::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_end(&mut data))
+            .chain_err(|| format!("Failed to read file {}", source))?;
+
+        Bundle::from_bytes(&data, &source)
+    }
+}
@@ -0,0 +1,176 @@
+//! The command line interface of the `oxidoc` binary.
+
+use clap::{App, Arg, SubCommand};
+
+use search;
+
+/// Defines the arguments and subcommands of `oxidoc`. Subcommands take precedence over a search
+/// query with the same name, which can still be searched for by putting `--` before it.
+pub fn app<'a, 'b>() -> App<'a, 'b> {
+    App::new(format!("oxidoc {}", crate_version!()))
+        .about("A command line interface to Rustdoc.")
+        .arg(Arg::with_name("version").short("V").long("version").help(
+            "Prints version info",
+        ))
+        .arg(Arg::with_name("tui").short("t").long("tui").help(
+            "Starts interactive console user interface",
+        ))
+        .arg(
+            Arg::with_name("generate")
+                .short("g")
+                .long("generate")
+                .value_name("CRATE_DIR")
+                .help(
+                    "Generate oxidoc info for the specified crate root directory, 'std' for stdlib \
+                    (requires RUST_SRC_PATH to be set), 'crates' for all cargo crates or 'all' \
+                    for everything",
+                )
+                .takes_value(true)
+                .alias("generate"),
+        )
+        .arg(
+            Arg::with_name("store")
+                .long("store")
+                .value_name("DIR")
+                .help(
+                    "Uses DIR as the writable documentation store instead of OXIDOC_STORE, the \
+                    config file or ~/.cargo/registry/doc",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("complete")
+                .long("complete")
+                .value_name("PREFIX")
+                .help(
+                    "Prints the module paths starting with PREFIX, like 'std::coll', or the paths \
+                    of items whose names start with it, like 'HashM'",
+                )
+                .takes_value(true),
+        )
+        .arg(Arg::with_name("document-private-items").long("document-private-items").help(
+            "When generating, also index private and #[doc(hidden)] items",
+        ))
+        .arg(Arg::with_name("private").short("P").long("private").help(
+            "Includes private and hidden items in search results",
+        ))
+        .arg(
+            Arg::with_name("kind")
+                .short("k")
+                .long("kind")
+                .value_name("KIND")
+                .help("Only returns items of this kind. Can be given several times")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .possible_values(search::KIND_NAMES),
+        )
+        .arg(
+            Arg::with_name("crate")
+                .long("crate")
+                .value_name("CRATE[@VERSION]")
+                .help(
+                    "Only returns items from this crate, and from versions matching VERSION if \
+                    given, like 'tokio@0.1'",
+                )
+                .takes_value(true),
+        )
+        .arg(Arg::with_name("unsafe").long("unsafe").help(
+            "Only returns unsafe functions, methods and traits",
+        ))
+        .arg(Arg::with_name("const-fn").long("const-fn").help(
+            "Only returns const functions and methods",
+        ))
+        .arg(Arg::with_name("text").long("text").help(
+            "Searches the text of the documentation instead of item names",
+        ))
+        .arg(Arg::with_name("signature").short("s").long("signature").conflicts_with("text").help(
+            "Searches functions by type signature, like \"&str -> Result<u32, _>\"",
+        ))
+        .arg(Arg::with_name("pager").short("p").long("pager").help(
+            "Automatically pages output",
+        ))
+        .arg(Arg::with_name("source").long("source").help(
+            "Shows the source of default method bodies",
+        ))
+        .arg(Arg::with_name("brief").short("b").long("brief").help(
+            "Shows only the best match in full, and the signatures and summaries of the others",
+        ))
+        .arg(Arg::with_name("query").index(1))
+        .subcommand(
+            SubCommand::with_name("remove")
+                .about("Removes the documentation for a crate or a single version of a crate")
+                .arg(
+                    Arg::with_name("crate")
+                        .value_name("CRATE[@VERSION]")
+                        .required_unless("all")
+                        .conflicts_with("all")
+                        .index(1),
+                )
+                .arg(Arg::with_name("all").long("all").help(
+                    "Removes the documentation for every crate",
+                ))
+                .arg(
+                    Arg::with_name("keep")
+                        .long("keep")
+                        .value_name("N")
+                        .help("Keeps the newest N versions of each crate")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about(
+                    "Exports the documentation for a crate version into a bundle which can be \
+                    imported on other machines, by default the newest version",
+                )
+                .arg(
+                    Arg::with_name("crate")
+                        .value_name("CRATE[@VERSION]")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("FILE")
+                        .help("Writes the bundle to FILE instead of CRATE-VERSION.oxdoc")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("import")
+                .about("Imports a documentation bundle created with export into the store")
+                .arg(Arg::with_name("file").value_name("FILE").required(true).index(1)),
+        )
+        .subcommand(
+            SubCommand::with_name("doctor")
+                .about(
+                    "Checks the documentation store for missing, corrupt and orphaned \
+                    documentation",
+                )
+                .arg(Arg::with_name("repair").long("repair").help(
+                    "Removes the broken documentation found from the store",
+                )),
+        )
+        .subcommand(
+            SubCommand::with_name("list")
+                .about(
+                    "Lists the documented crates and their versions, or the number of items of \
+                    each kind in the versions of a crate",
+                )
+                .arg(Arg::with_name("crate").value_name("CRATE[@VERSION]").index(1)),
+        )
+        .subcommand(
+            SubCommand::with_name("tree")
+                .about(
+                    "Prints the items below a crate or module path, like 'serde::de' or \
+                    'serde@1.0.9::de'",
+                )
+                .arg(Arg::with_name("path").value_name("PATH").required(true).index(1))
+                .arg(Arg::with_name("private").short("P").long("private").help(
+                    "Includes private and hidden items",
+                )),
+        )
+}
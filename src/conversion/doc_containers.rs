@@ -1,15 +1,12 @@
 use store::StoreLocation;
 use std::collections::HashMap;
-use std::fs;
 use std::fmt::{self, Display};
 
 use document::{CrateInfo, ModPath};
-use generation::ast_ty_wrappers::Attributes;
-use store;
+use generation::ast_ty_wrappers::{Attributes, FnKind, Ty, TyKind};
 
 use conversion::wrappers::*;
 use conversion::wrappers::TraitItemKind;
-use ::errors::*;
 
 pub use self::DocInnerData::*;
 
@@ -29,11 +26,44 @@ pub struct Documentation {
 
 impl Display for Visibility {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let vis = match *self {
-            Visibility::Public => "pub",
-            _                  => "",
+        match *self {
+            Visibility::Public               => write!(f, "pub"),
+            Visibility::Crate                => write!(f, "pub(crate)"),
+            Visibility::Restricted(ref path) => {
+                // `pub(self)` and `pub(super)` are written without `in`.
+                match path.to_string().as_str() {
+                    "self" | "super" | "crate" => write!(f, "pub({})", path),
+                    _                          => write!(f, "pub(in {})", path),
+                }
+            },
+            Visibility::Inherited            => Ok(()),
+        }
+    }
+}
+
+impl Display for Abi {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Abi::Cdecl             => "cdecl",
+            Abi::Stdcall           => "stdcall",
+            Abi::Fastcall          => "fastcall",
+            Abi::Vectorcall        => "vectorcall",
+            Abi::Aapcs             => "aapcs",
+            Abi::Win64             => "win64",
+            Abi::SysV64            => "sysv64",
+            Abi::PtxKernel         => "ptx-kernel",
+            Abi::Msp430Interrupt   => "msp430-interrupt",
+            Abi::X86Interrupt      => "x86-interrupt",
+            Abi::Rust              => "Rust",
+            Abi::C                 => "C",
+            Abi::System            => "system",
+            Abi::RustIntrinsic     => "rust-intrinsic",
+            Abi::RustCall          => "rust-call",
+            Abi::PlatformIntrinsic => "platform-intrinsic",
+            Abi::Unadjusted        => "unadjusted",
+            Abi::Thiscall          => "thiscall",
         };
-        write!(f, "{}", vis)
+        write!(f, "\"{}\"", name)
     }
 }
 
@@ -52,6 +82,9 @@ impl Documentation {
             DocInnerData::StructDoc(..) => {
                 DocType::Struct
             },
+            DocInnerData::PrimitiveDoc(..) => {
+                DocType::Primitive
+            },
             DocInnerData::ConstDoc(..) => {
                 DocType::Const
             },
@@ -70,9 +103,82 @@ impl Documentation {
         }
     }
 
-    // TODO: Better way for formatting the wrapped types, as pprust does.
-    fn subitems(&self) -> String {
-        let categories = match self.inner_data {
+    /// Whether this item is outside of the crate's public API, either because it isn't `pub` or
+    /// because it was marked `#[doc(hidden)]`.
+    pub fn is_private(&self) -> bool {
+        if self.attrs.hidden {
+            return true;
+        }
+
+        // Trait items carry the visibility of the trait they belong to.
+        match self.visibility {
+            Some(Visibility::Public) | None => false,
+            Some(_) => true,
+        }
+    }
+
+    /// The full paths of the types in this item's signature that could be resolved within its
+    /// crate, without duplicates.
+    pub fn referenced_types(&self) -> Vec<ModPath> {
+        let mut tys: Vec<&Ty> = Vec::new();
+
+        match self.inner_data {
+            DocInnerData::FnDoc(ref func) => tys.extend(func.decl.types()),
+            DocInnerData::ConstDoc(ref konst) => tys.push(&konst.ty),
+            DocInnerData::StructDoc(ref struct_) => tys.extend(struct_.fields.iter().map(|f| &f.ty)),
+            DocInnerData::TraitItemDoc(ref item) => {
+                match item.node {
+                    TraitItemKind::Const(ref ty, _)      => tys.push(ty),
+                    TraitItemKind::Method(ref sig, _)    => tys.extend(sig.decl.types()),
+                    TraitItemKind::Type(_, Some(ref ty)) => tys.push(ty),
+                    TraitItemKind::Type(_, None) |
+                    TraitItemKind::Macro(..)             => (),
+                }
+            },
+            DocInnerData::ModuleDoc(..) |
+            DocInnerData::EnumDoc(..) |
+            DocInnerData::PrimitiveDoc(..) |
+            DocInnerData::TraitDoc(..) => (),
+        }
+
+        let mut paths = Vec::new();
+        for path in tys.iter().flat_map(|ty| ty.resolved_paths()) {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+        paths
+    }
+
+    /// The `Target` of this type's `Deref` impl as it was written, if it has one.
+    pub fn deref_target_type(&self) -> Option<&Ty> {
+        match self.inner_data {
+            DocInnerData::StructDoc(ref struct_) => struct_.deref_target.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// The path of the type this one dereferences to through `Deref`, if any. This is the full
+    /// path if it could be resolved within the crate, or the path as written otherwise. Slices
+    /// are documented as the `slice` primitive. Type parameters like the `T` of `Box<T>` can't be
+    /// looked up, so there is no path for them.
+    pub fn deref_target(&self) -> Option<ModPath> {
+        match self.inner_data {
+            DocInnerData::StructDoc(ref struct_) if struct_.deref_target_is_generic => return None,
+            _ => (),
+        }
+
+        self.deref_target_type().and_then(|ty| match ty.kind {
+            TyKind::Path(ref path) => Some(path.resolved.clone().unwrap_or(path.path.clone())),
+            TyKind::Slice(..) => Some(ModPath::from("slice".to_string())),
+            _ => None,
+        })
+    }
+
+    /// The categories of related items that are shown for this kind of documentation, in
+    /// display order.
+    pub fn subitem_categories(&self) -> Vec<DocType> {
+        match self.inner_data {
             // NOTE: Any better way to just enumerate all DocType values? This
             // violates OCP.
             DocInnerData::ModuleDoc(..) => {
@@ -84,14 +190,16 @@ impl Documentation {
                      DocType::Const]
             },
             DocInnerData::TraitDoc(..) => {
-                vec![DocType::AssocConst,
+                vec![DocType::AssocType,
+                     DocType::AssocConst,
                      DocType::TraitItemMethod,
-                     DocType::AssocType,
+                     DocType::ProvidedMethod,
                      DocType::Macro]
             },
             DocInnerData::StructDoc(..) => {
                 vec![DocType::StructField,
                      DocType::Function,
+                     DocType::DerefMethod,
                      DocType::AssocConst,
                      DocType::AssocType,
                      DocType::Macro]
@@ -100,51 +208,140 @@ impl Documentation {
                 vec![DocType::Function,
                      DocType::Variant]
             },
+            DocInnerData::PrimitiveDoc(..) => {
+                vec![DocType::Function,
+                     DocType::AssocConst]
+            },
             _  => vec![]
-        };
+        }
+    }
 
-        categories.iter().map(|c| self.subitems_in_category(c))
-            .filter(|c| c.is_some())
-            .map(|c| c.unwrap())
-            .collect::<Vec<String>>().join("\n\n")
+    /// Whether this is an unsafe function, method or trait.
+    pub fn is_unsafe(&self) -> bool {
+        let unsafety = match self.inner_data {
+            DocInnerData::FnDoc(ref func) => &func.unsafety,
+            DocInnerData::TraitDoc(ref trait_) => &trait_.unsafety,
+            DocInnerData::TraitItemDoc(ref item) => match item.node {
+                TraitItemKind::Method(ref sig, _) => &sig.unsafety,
+                _ => return false,
+            },
+            _ => return false,
+        };
+        *unsafety == Unsafety::Unsafe
     }
 
-    fn subitems_in_category(&self, type_: &DocType) -> Option<String> {
-        if let Some(items) = self.links.get(type_) {
-            if items.len() > 0 {
-                let category_str = type_.to_string();
-                let items_str = items.iter().cloned().map(|i| i.name ).collect::<Vec<String>>().join("\n");
-                Some(format!("==== {}\n{}", category_str, items_str))
-            } else {
-                None
-            }
-        } else {
-            None
-        }
+    /// Whether this is a `const fn`.
+    pub fn is_const_fn(&self) -> bool {
+        let constness = match self.inner_data {
+            DocInnerData::FnDoc(ref func) => &func.constness,
+            DocInnerData::TraitItemDoc(ref item) => match item.node {
+                TraitItemKind::Method(ref sig, _) => &sig.constness,
+                _ => return false,
+            },
+            _ => return false,
+        };
+        *constness == Constness::Const
     }
 
-    pub fn to_store_location(&self) -> StoreLocation {
-        StoreLocation {
-            name: self.name.clone(),
-            crate_info: self.crate_info.clone(),
-            mod_path: self.mod_path.clone(),
-            doc_type: self.get_type(),
+    /// Whether this is a method of a type or trait rather than a free function.
+    pub fn is_method(&self) -> bool {
+        match self.inner_data {
+            DocInnerData::FnDoc(ref func) => func.kind != FnKind::ItemFn,
+            DocInnerData::TraitItemDoc(ref item) => match item.node {
+                TraitItemKind::Method(..) => true,
+                _ => false,
+            },
+            _ => false,
         }
     }
 
-    pub fn save(&self) -> Result<()> {
-        let location = self.to_store_location();
-        let path = location.to_filepath();
+    /// Renders the signature of the item on a single line, without highlighting, bodies or
+    /// fields. It's kept in the store index, so lists of results can show it without loading the
+    /// documentation.
+    pub fn short_signature(&self) -> String {
+        let signature = match self.inner_data {
+            DocInnerData::ModuleDoc(ref module) => {
+                if module.is_crate {
+                    format!("crate {}", self.name)
+                } else {
+                    format!("mod {}", self.name)
+                }
+            }
+            DocInnerData::FnDoc(ref func) => {
+                let mut words = fn_qualifiers(&func.constness, &func.asyncness, &func.unsafety, &func.abi);
+                words.push(format!("fn {} {}", self.name, func.header));
+                words.join(" ")
+            }
+            DocInnerData::EnumDoc(..) => format!("enum {}", self.name),
+            DocInnerData::StructDoc(..) => format!("struct {}", self.name),
+            DocInnerData::PrimitiveDoc(..) => format!("primitive {}", self.name),
+            DocInnerData::ConstDoc(ref konst) => format!("const {}: {}", self.name, konst.ty.name),
+            DocInnerData::TraitDoc(ref trait_) => {
+                match trait_.unsafety {
+                    Unsafety::Unsafe => format!("unsafe trait {}", self.name),
+                    Unsafety::Normal => format!("trait {}", self.name),
+                }
+            }
+            DocInnerData::TraitItemDoc(ref item) => {
+                match item.node {
+                    TraitItemKind::Const(ref ty, _) => format!("const {}: {}", self.name, ty.name),
+                    TraitItemKind::Method(ref sig, _) => {
+                        let mut words = fn_qualifiers(&sig.constness, &sig.asyncness, &sig.unsafety, &sig.abi);
+                        words.push(format!("fn {} {}", self.name, sig.header));
+                        words.join(" ")
+                    }
+                    TraitItemKind::Type(ref bounds, _) => {
+                        if bounds.is_empty() {
+                            format!("type {}", self.name)
+                        } else {
+                            format!("type {}: {}", self.name, bounds.join(" + "))
+                        }
+                    }
+                    TraitItemKind::Macro(..) => format!("macro {}", self.name),
+                }
+            }
+        };
+
+        signature.split_whitespace().collect::<Vec<&str>>().join(" ")
+    }
 
-        {
-            let parent_path = path.parent().unwrap();
+    pub fn to_store_location(&self) -> StoreLocation {
+        let mut location = StoreLocation::new(self.name.clone(),
+                                              self.crate_info.clone(),
+                                              self.mod_path.clone(),
+                                              self.get_type(),
+                                              self.is_private());
+        location.summary = self.attrs.summary();
+        location.signature = self.short_signature();
+        location.is_unsafe = self.is_unsafe();
+        location.is_const_fn = self.is_const_fn();
+        location.is_method = self.is_method();
+        location
+    }
+}
 
-            fs::create_dir_all(parent_path)
-                .chain_err(|| format!("Failed to create directory {}", parent_path.display()))?;
-        }
+/// Returns the qualifiers that come before `fn`, like `const`, `unsafe` and `extern "C"`, in
+/// order.
+pub fn fn_qualifiers(constness: &Constness,
+                     asyncness: &Asyncness,
+                     unsafety: &Unsafety,
+                     abi: &Abi) -> Vec<String> {
+    let mut qualifiers = Vec::new();
 
-        store::serialize_object(self, path)
+    if *constness == Constness::Const {
+        qualifiers.push("const".to_string());
+    }
+    if *asyncness == Asyncness::Async {
+        qualifiers.push("async".to_string());
+    }
+    if *unsafety == Unsafety::Unsafe {
+        qualifiers.push("unsafe".to_string());
     }
+    if *abi != Abi::Rust {
+        qualifiers.push(format!("extern {}", abi));
+    }
+
+    qualifiers
 }
 
 /// A list of documentation locations related to the current item, like methods implemented on
@@ -171,11 +368,17 @@ pub enum DocType {
     Trait,
     AssocConst,
     TraitItemMethod,
+    /// Trait methods with a default body. Only used to categorize links on trait documentation.
+    ProvidedMethod,
     TraitItemConst,
     TraitItemType,
     TraitItemMacro,
     AssocType,
     Macro,
+    /// Methods available through the item's `Deref` target. Only used to categorize links, and
+    /// added when the documentation is displayed.
+    DerefMethod,
+    Primitive,
 }
 
 impl DocType {
@@ -192,10 +395,37 @@ impl DocType {
             DocType::AssocConst  => &"acdesc-",
             DocType::TraitItemConst => &"tcdesc-",
             DocType::TraitItemMethod => &"tmcdesc-",
+            DocType::ProvidedMethod => &"pmdesc-",
             DocType::TraitItemType => &"ttcdesc-",
             DocType::TraitItemMacro => &"tmdesc-",
             DocType::AssocType   => &"atdesc-",
             DocType::Macro  => &"macdesc-",
+            DocType::DerefMethod => &"dmdesc-",
+            DocType::Primitive => &"pdesc-",
+        }
+    }
+
+    /// The name of a single item of this kind, for listings.
+    pub fn kind_name(&self) -> &str {
+        match *self {
+            DocType::Function => "function",
+            DocType::Module => "module",
+            DocType::Enum => "enum",
+            DocType::Variant => "variant",
+            DocType::Struct => "struct",
+            DocType::StructField => "field",
+            DocType::Const => "constant",
+            DocType::Trait => "trait",
+            DocType::AssocConst  => "associated constant",
+            DocType::TraitItemConst => "trait constant",
+            DocType::TraitItemMethod => "required method",
+            DocType::ProvidedMethod => "provided method",
+            DocType::TraitItemType => "trait type",
+            DocType::TraitItemMacro => "trait macro",
+            DocType::AssocType   => "associated type",
+            DocType::Macro  => "macro",
+            DocType::DerefMethod => "method from Deref",
+            DocType::Primitive => "primitive type",
         }
     }
 }
@@ -213,11 +443,14 @@ impl Display for DocType {
             DocType::Trait => "Traits",
             DocType::AssocConst  => &"Associated Constants",
             DocType::TraitItemConst => &"Trait Constants",
-            DocType::TraitItemMethod => &"Trait Methods",
+            DocType::TraitItemMethod => &"Required Methods",
+            DocType::ProvidedMethod => &"Provided Methods",
             DocType::TraitItemType => &"Trait Types",
             DocType::TraitItemMacro => &"Trait Macros",
             DocType::AssocType   => &"Associated Types",
             DocType::Macro  => &"Macros",
+            DocType::DerefMethod => &"Methods from Deref",
+            DocType::Primitive => &"Primitive Types",
         };
         write!(f, "{}", name)
     }
@@ -236,4 +469,5 @@ pub enum DocInnerData {
     //TypedefDoc,
     TraitDoc(Trait),
     TraitItemDoc(TraitItem),
+    PrimitiveDoc(Primitive),
 }
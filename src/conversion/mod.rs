@@ -7,14 +7,14 @@ mod doc_containers;
 pub use conversion::doc_containers::*;
 
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::rc::Rc;
 
 use syntax::abi;
 use syntax::ast;
 use syntax::print::pprust;
 use syntax::ptr::P;
 
-use generation::ast_ty_wrappers::{self, Impl, Ty, Attributes};
+use generation::ast_ty_wrappers::{self, Impl, Ty, TyKind, TyPath, Mutability, NodeId, Attributes};
 use generation::visitor::OxidocVisitor;
 use document::{CrateInfo, ModPath};
 
@@ -22,22 +22,42 @@ pub use conversion::wrappers::*;
 
 #[derive(Clone)]
 pub struct Context {
-    pub store_path: PathBuf,
     pub crate_info: CrateInfo,
     /// Mapping from types to their implementations. Received from the AST visitor.
-    pub impls_for_ty: HashMap<ModPath, Vec<Impl>>,
+    pub impls_for_ty: Rc<HashMap<ModPath, Vec<Impl>>>,
+    /// The identifiers that are 'use'd in the module currently being converted, used for
+    /// resolving the paths inside types.
+    pub namespaces_to_paths: HashMap<String, ModPath>,
 }
 
 impl Context {
-    pub fn new(store_path: PathBuf,
-               crate_info: CrateInfo,
+    pub fn new(crate_info: CrateInfo,
                impls_for_ty: HashMap<ModPath, Vec<Impl>>) -> Self {
         Context {
-            store_path: store_path,
             crate_info: crate_info,
-            impls_for_ty: impls_for_ty,
+            impls_for_ty: Rc::new(impls_for_ty),
+            namespaces_to_paths: HashMap::new(),
         }
     }
+
+    /// Returns a context for converting the items inside the given module.
+    pub fn for_module(&self, module: &ast_ty_wrappers::Module) -> Self {
+        let mut context = self.clone();
+        context.namespaces_to_paths = module.namespaces_to_paths.clone();
+        context
+    }
+
+    /// Resolves a path as written inside the current module to its full path in the crate.
+    pub fn resolve_path(&self, path: &ModPath) -> Option<ModPath> {
+        let ident = match path.head() {
+            Some(segment) => segment.identifier,
+            None          => return None,
+        };
+
+        self.namespaces_to_paths.get(&ident)
+            .and_then(|u| u.parent())
+            .map(|parent| ModPath::join(&parent, path))
+    }
 }
 
 pub trait Convert<T> {
@@ -84,8 +104,11 @@ impl Convert<Visibility> for ast::Visibility {
     fn convert(&self, _context: &Context) -> Visibility {
         match *self {
             ast::Visibility::Public    => Visibility::Public,
+            ast::Visibility::Crate(..) => Visibility::Crate,
+            ast::Visibility::Restricted { ref path, .. } => {
+                Visibility::Restricted(ModPath::from((**path).clone()))
+            },
             ast::Visibility::Inherited => Visibility::Inherited,
-            _                          => Visibility::Private,
         }
     }
 }
@@ -134,15 +157,19 @@ impl Convert<Vec<Documentation>> for ast_ty_wrappers::Module {
             debug!("in {:?}, {} => {}", self.ident, ident, path);
         }
 
+        let context = &context.for_module(self);
+
         let mut docs: Vec<Documentation> = vec![];
 
         docs.extend(self.consts.iter().map(|x| x.convert(context)));
         docs.extend(self.traits.iter().map(|x| x.convert(context)));
+        docs.extend(self.traits.iter().flat_map(|x| -> Vec<Documentation> { x.items.convert(context) }));
         docs.extend(self.fns.iter().map(|x| x.convert(context)));
         docs.extend(self.mods.iter().flat_map(|x| x.convert(context)));
         docs.extend(self.structs.iter().map(|x| x.convert(context)));
         // unions
         docs.extend(self.enums.iter().map(|x| x.convert(context)));
+        docs.extend(self.primitives.iter().map(|x| x.convert(context)));
         // foreigns
         // typedefs
         // statics
@@ -181,7 +208,7 @@ impl Convert<Documentation> for ast_ty_wrappers::Constant {
             crate_info: context.crate_info.clone(),
             visibility: Some(self.vis.convert(context)),
             inner_data: ConstDoc(Constant {
-                ty: self.type_.clone(),
+                ty: self.type_.convert(context),
                 expr: self.expr.convert(context),
             }),
             links: HashMap::new(),
@@ -199,9 +226,13 @@ impl Convert<Documentation> for ast_ty_wrappers::Function {
             visibility: Some(self.vis.convert(context)),
             inner_data: FnDoc(Function {
                 header: self.decl.convert(context),
+                decl: self.decl.convert(context),
                 generics: Generics { } ,
                 unsafety: self.unsafety.convert(context),
                 constness: self.constness.convert(context),
+                // TODO: syntex_syntax can't parse `async fn` yet. Detect it
+                // here once the parser supports it.
+                asyncness: Asyncness::NotAsync,
                 abi: self.abi.convert(context),
                 kind: self.kind.clone(),
             }),
@@ -215,8 +246,10 @@ impl Convert<MethodSig> for ast::MethodSig {
         MethodSig {
             unsafety: self.unsafety.convert(context),
             constness: self.constness.node.convert(context),
+            asyncness: Asyncness::NotAsync,
             abi: self.abi.convert(context),
             header: self.decl.convert(context),
+            decl: self.decl.convert(context),
         }
     }
 }
@@ -244,7 +277,7 @@ impl Convert<Documentation> for ast_ty_wrappers::TraitItem {
             attrs: self.attrs.convert(context),
             mod_path: self.path.clone(),
             crate_info: context.crate_info.clone(),
-            visibility: Some(Visibility::Inherited),
+            visibility: Some(self.vis.convert(context)),
             inner_data: TraitItemDoc(TraitItem {
                 node: self.node.convert(context),
             }),
@@ -256,14 +289,16 @@ impl Convert<Documentation> for ast_ty_wrappers::TraitItem {
 impl Convert<DocRelatedItems> for [ast_ty_wrappers::TraitItem] {
     fn convert(&self, context: &Context) -> DocRelatedItems {
         let mut consts = Vec::new();
-        let mut methods = Vec::new();
+        let mut required_methods = Vec::new();
+        let mut provided_methods = Vec::new();
         let mut types = Vec::new();
         let mut macros = Vec::new();
 
         for item in self {
             match item.node {
                 ast::TraitItemKind::Const(..) => consts.push(item.clone()),
-                ast::TraitItemKind::Method(..) => methods.push(item.clone()),
+                ast::TraitItemKind::Method(_, None) => required_methods.push(item.clone()),
+                ast::TraitItemKind::Method(_, Some(..)) => provided_methods.push(item.clone()),
                 ast::TraitItemKind::Type(..) => types.push(item.clone()),
                 ast::TraitItemKind::Macro(..) => macros.push(item.clone()),
             }
@@ -280,7 +315,8 @@ impl Convert<DocRelatedItems> for [ast_ty_wrappers::TraitItem] {
 
         let mut links = HashMap::new();
         links.insert(DocType::AssocConst, conv(consts));
-        links.insert(DocType::TraitItemMethod, conv(methods));
+        links.insert(DocType::TraitItemMethod, conv(required_methods));
+        links.insert(DocType::ProvidedMethod, conv(provided_methods));
         links.insert(DocType::AssocType, conv(types));
         links.insert(DocType::Macro, conv(macros));
         links
@@ -293,11 +329,15 @@ impl Convert<TraitItemKind> for ast::TraitItemKind {
             ast::TraitItemKind::Const(ref ty, ref expr) => {
                 TraitItemKind::Const(ty.convert(context), expr.convert(context))
             },
-            ast::TraitItemKind::Method(ref sig, ref _block) => {
-                TraitItemKind::Method(sig.convert(context))
+            ast::TraitItemKind::Method(ref sig, ref block) => {
+                let body = block.as_ref().map(|b| pprust::block_to_string(b));
+                TraitItemKind::Method(sig.convert(context), body)
             },
-            ast::TraitItemKind::Type(ref _bounds, ref ty) => {
-                TraitItemKind::Type(ty.convert(context))
+            ast::TraitItemKind::Type(ref bounds, ref ty) => {
+                let bounds = bounds.iter()
+                    .map(|b| pprust::bounds_to_string(&[b.clone()]).trim().to_string())
+                    .collect();
+                TraitItemKind::Type(bounds, ty.convert(context))
             },
             ast::TraitItemKind::Macro(ref mac) => {
                 TraitItemKind::Macro(mac.convert(context))
@@ -309,11 +349,14 @@ impl Convert<TraitItemKind> for ast::TraitItemKind {
 impl Convert<Documentation> for ast_ty_wrappers::Struct {
     fn convert(&self, context: &Context) -> Documentation {
         let mut links: DocRelatedItems = self.fields.convert(context);
+        add_impl_links(&mut links, &self.path, context);
+
+        let mut deref_target = None;
+        let mut deref_target_is_generic = false;
         if let Some(impls) = context.impls_for_ty.get(&self.path) {
-            for impl_ in impls {
-                let impl_links = impl_.convert(context);
-                debug!("Impl found for {}!", self.path);
-                links.extend(impl_links);
+            if let Some((impl_, ty)) = find_deref_target(impls) {
+                deref_target = Some(ty.convert(context));
+                deref_target_is_generic = is_type_parameter(ty, &impl_.generics);
             }
         }
 
@@ -325,12 +368,77 @@ impl Convert<Documentation> for ast_ty_wrappers::Struct {
             visibility: Some(self.vis.convert(context)),
             inner_data: StructDoc(Struct {
                 fields: self.fields.convert(context),
+                deref_target: deref_target,
+                deref_target_is_generic: deref_target_is_generic,
             }),
             links: links,
         }
     }
 }
 
+impl Convert<Documentation> for ast_ty_wrappers::Primitive {
+    fn convert(&self, context: &Context) -> Documentation {
+        let mut links = HashMap::new();
+        add_impl_links(&mut links, &self.path, context);
+
+        Documentation {
+            name: self.name.clone(),
+            attrs: Attributes::new(),
+            mod_path: self.path.clone(),
+            crate_info: context.crate_info.clone(),
+            visibility: Some(Visibility::Public),
+            inner_data: PrimitiveDoc(Primitive),
+            links: links,
+        }
+    }
+}
+
+/// Adds links to the items of the impls for the type at `path`.
+fn add_impl_links(links: &mut DocRelatedItems, path: &ModPath, context: &Context) {
+    if let Some(impls) = context.impls_for_ty.get(path) {
+        for impl_ in impls {
+            let impl_links: DocRelatedItems = impl_.convert(context);
+            debug!("Impl found for {}!", path);
+            // Several impls can add items of the same kind, so merge them.
+            for (doc_type, items) in impl_links {
+                links.entry(doc_type).or_insert(Vec::new()).extend(items);
+            }
+        }
+    }
+}
+
+/// Finds `Y` in `impl Deref for X { type Target = Y; }` among the impls of a type, along with
+/// the impl it was found in.
+fn find_deref_target(impls: &[Impl]) -> Option<(&Impl, &ast::Ty)> {
+    let is_deref = |impl_: &&Impl| {
+        impl_.trait_.as_ref()
+            .and_then(|trait_ref| trait_ref.path.segments.last())
+            .map_or(false, |seg| pprust::ident_to_string(seg.identifier) == "Deref")
+    };
+
+    impls.iter()
+        .filter(is_deref)
+        .flat_map(|impl_| impl_.items.iter().map(move |item| (impl_, item)))
+        .filter_map(|(impl_, item)| match item.node {
+            ast::ImplItemKind::Type(ref ty) if pprust::ident_to_string(item.ident) == "Target" => {
+                Some((impl_, &**ty))
+            },
+            _ => None,
+        })
+        .next()
+}
+
+/// Whether a type is one of the type parameters in `generics`, like `T` in `impl<T> Box<T>`.
+fn is_type_parameter(ty: &ast::Ty, generics: &ast::Generics) -> bool {
+    match ty.node {
+        ast::TyKind::Path(None, ref path) if path.segments.len() == 1 => {
+            let ident = path.segments[0].identifier;
+            generics.ty_params.iter().any(|param| param.ident == ident)
+        },
+        _ => false,
+    }
+}
+
 impl Convert<DocRelatedItems> for ast_ty_wrappers::Impl {
     fn convert(&self, context: &Context) -> DocRelatedItems {
         let mut consts = Vec::new();
@@ -408,7 +516,7 @@ impl Convert<Documentation> for ast_ty_wrappers::Enum {
             attrs: self.attrs.convert(context),
             mod_path: self.path.clone(),
             crate_info: context.crate_info.clone(),
-            visibility: Some(Visibility::Inherited),
+            visibility: Some(self.vis.convert(context)),
             inner_data: EnumDoc(Enum {
                 variants: self.variants.convert(context),
             }),
@@ -418,8 +526,130 @@ impl Convert<Documentation> for ast_ty_wrappers::Enum {
 }
 
 impl Convert<Ty> for ast::Ty {
-    fn convert(&self, _context: &Context) -> Ty {
-        Ty::from(self.clone())
+    fn convert(&self, context: &Context) -> Ty {
+        Ty {
+            id: NodeId::from(self.id),
+            name: pprust::ty_to_string(self),
+            kind: self.node.convert(context),
+        }
+    }
+}
+
+impl Convert<TyKind> for ast::TyKind {
+    fn convert(&self, context: &Context) -> TyKind {
+        match *self {
+            ast::TyKind::Slice(ref ty) => TyKind::Slice(Box::new(ty.convert(context))),
+            ast::TyKind::Array(ref ty, ref len) => {
+                TyKind::Array(Box::new(ty.convert(context)), len.convert(context))
+            },
+            ast::TyKind::Ptr(ref mut_ty) => {
+                TyKind::Ptr(mut_ty.mutbl.convert(context), Box::new(mut_ty.ty.convert(context)))
+            },
+            ast::TyKind::Rptr(ref lifetime, ref mut_ty) => {
+                let lifetime = lifetime.as_ref().map(|l| pprust::lifetime_to_string(l));
+                TyKind::Ref(lifetime,
+                            mut_ty.mutbl.convert(context),
+                            Box::new(mut_ty.ty.convert(context)))
+            },
+            ast::TyKind::BareFn(ref bare_fn) => {
+                let args = bare_fn.decl.inputs.iter().map(|arg| arg.ty.convert(context)).collect();
+                let ret = match bare_fn.decl.output {
+                    ast::FunctionRetTy::Ty(ref ty)   => Some(Box::new(ty.convert(context))),
+                    ast::FunctionRetTy::Default(..) => None,
+                };
+                TyKind::BareFn(args, ret)
+            },
+            ast::TyKind::Never => TyKind::Never,
+            ast::TyKind::Tup(ref tys) => {
+                TyKind::Tuple(tys.iter().map(|ty| ty.convert(context)).collect())
+            },
+            ast::TyKind::Path(_, ref path) => TyKind::Path(path.convert(context)),
+            ast::TyKind::TraitObject(ref bounds, ..) => {
+                TyKind::TraitObject(convert_trait_bounds(bounds, context))
+            },
+            ast::TyKind::ImplTrait(ref bounds) => {
+                TyKind::ImplTrait(convert_trait_bounds(bounds, context))
+            },
+            ast::TyKind::Paren(ref ty) => ty.node.convert(context),
+            ast::TyKind::Infer => TyKind::Infer,
+            ast::TyKind::ImplicitSelf => TyKind::ImplicitSelf,
+            _ => TyKind::Other,
+        }
+    }
+}
+
+impl Convert<TyPath> for ast::Path {
+    fn convert(&self, context: &Context) -> TyPath {
+        let path = ModPath::from(self.clone());
+        let mut args = Vec::new();
+        let mut bindings = Vec::new();
+
+        // Only the final segment's parameters are kept, since those are the
+        // ones that belong to the type itself.
+        if let Some(params) = self.segments.last().and_then(|seg| seg.parameters.as_ref()) {
+            match **params {
+                ast::PathParameters::AngleBracketed(ref data) => {
+                    args = data.types.iter().map(|ty| ty.convert(context)).collect();
+                    bindings = data.bindings.iter()
+                        .map(|b| (b.ident.convert(context), b.ty.convert(context)))
+                        .collect();
+                },
+                ast::PathParameters::Parenthesized(ref data) => {
+                    args = data.inputs.iter().map(|ty| ty.convert(context)).collect();
+                    if let Some(ref output) = data.output {
+                        bindings.push(("Output".to_string(), output.convert(context)));
+                    }
+                },
+            }
+        }
+
+        TyPath {
+            resolved: context.resolve_path(&path),
+            path: path,
+            args: args,
+            bindings: bindings,
+        }
+    }
+}
+
+/// Converts the trait bounds in a list of type parameter bounds, ignoring lifetimes.
+fn convert_trait_bounds(bounds: &[ast::TyParamBound], context: &Context) -> Vec<TyPath> {
+    bounds.iter().filter_map(|bound| match *bound {
+        ast::TyParamBound::TraitTyParamBound(ref poly_trait, _) => {
+            Some(poly_trait.trait_ref.path.convert(context))
+        },
+        ast::TyParamBound::RegionTyParamBound(..) => None,
+    }).collect()
+}
+
+impl Convert<Mutability> for ast::Mutability {
+    fn convert(&self, _context: &Context) -> Mutability {
+        match *self {
+            ast::Mutability::Mutable   => Mutability::Mutable,
+            ast::Mutability::Immutable => Mutability::Immutable,
+        }
+    }
+}
+
+impl Convert<FnDecl> for ast::FnDecl {
+    fn convert(&self, context: &Context) -> FnDecl {
+        let inputs = self.inputs.iter().map(|arg| {
+            Argument {
+                name: pprust::pat_to_string(&arg.pat),
+                ty: arg.ty.convert(context),
+            }
+        }).collect();
+
+        let output = match self.output {
+            ast::FunctionRetTy::Ty(ref ty)   => Some(ty.convert(context)),
+            ast::FunctionRetTy::Default(..) => None,
+        };
+
+        FnDecl {
+            inputs: inputs,
+            output: output,
+            variadic: self.variadic,
+        }
     }
 }
 
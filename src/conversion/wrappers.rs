@@ -21,8 +21,33 @@ pub struct Enum {
 pub struct MethodSig {
     pub unsafety: Unsafety,
     pub constness: Constness,
+    pub asyncness: Asyncness,
     pub abi: Abi,
     pub header: String,
+    pub decl: FnDecl,
+}
+
+/// The argument and return types of a function signature.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct FnDecl {
+    pub inputs: Vec<Argument>,
+    /// The return type, or `None` for `()`.
+    pub output: Option<ast_ty_wrappers::Ty>,
+    pub variadic: bool,
+}
+
+impl FnDecl {
+    /// The types of all arguments followed by the return type.
+    pub fn types(&self) -> Vec<&ast_ty_wrappers::Ty> {
+        self.inputs.iter().map(|arg| &arg.ty).chain(self.output.iter()).collect()
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Argument {
+    /// The argument's pattern, usually just its name.
+    pub name: String,
+    pub ty: ast_ty_wrappers::Ty,
 }
 
 // There are redundant enums because it isn't possible to derive
@@ -39,10 +64,19 @@ pub enum Constness {
     NotConst,
 }
 
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Asyncness {
+    Async,
+    NotAsync,
+}
+
 #[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub enum Visibility {
     Public,
-    Private,
+    /// `pub(crate)`
+    Crate,
+    /// `pub(super)`, `pub(self)` or `pub(in path)`
+    Restricted(ModPath),
     Inherited,
 }
 
@@ -74,8 +108,17 @@ pub enum Abi {
 #[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Struct {
     pub fields: Vec<StructField>,
+    /// The `Target` of the struct's `Deref` impl, if it has one.
+    pub deref_target: Option<ast_ty_wrappers::Ty>,
+    /// Whether the `Target` is a type parameter of the impl, like `T` for `Box<T>`, so there is
+    /// no type to look it up as.
+    pub deref_target_is_generic: bool,
 }
 
+/// A primitive type like `str`, holding the inherent impls of a crate on it.
+#[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Primitive;
+
 // TODO: Should these have their own documentation?
 #[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub struct StructField {
@@ -100,9 +143,11 @@ pub struct Constant {
 #[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Function {
     pub header: String,
+    pub decl: FnDecl,
     pub generics: Generics,
     pub unsafety: Unsafety,
     pub constness: Constness,
+    pub asyncness: Asyncness,
     pub abi: Abi,
     pub kind: ast_ty_wrappers::FnKind,
 }
@@ -122,12 +167,24 @@ pub struct TraitItem {
 #[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub enum TraitItemKind {
     Const(ast_ty_wrappers::Ty, Option<String>),
-    Method(MethodSig),
-    Type(Option<ast_ty_wrappers::Ty>),
+    /// A method signature and the source of its default body, if it is a provided method.
+    Method(MethodSig, Option<String>),
+    /// The bounds on an associated type and its default, if any.
+    Type(Vec<String>, Option<ast_ty_wrappers::Ty>),
     Macro(String),
 }
 
 impl TraitItemKind {
+    /// Whether implementors of the trait have to provide this item themselves.
+    pub fn is_required(&self) -> bool {
+        match *self {
+            TraitItemKind::Const(_, ref default) => default.is_none(),
+            TraitItemKind::Method(_, ref body)   => body.is_none(),
+            TraitItemKind::Type(_, ref default)  => default.is_none(),
+            TraitItemKind::Macro(..)             => false,
+        }
+    }
+
     pub fn get_category_string(&self) -> &str {
         match *self {
             TraitItemKind::Const(..)  => &"const",
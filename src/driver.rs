@@ -1,5 +1,5 @@
-use conversion::Documentation;
-use store::{self, StoreLocation};
+use conversion::{Documentation, DocType};
+use store::{self, Store, StoreLocation};
 use errors::*;
 
 mod errors {
@@ -20,7 +20,23 @@ impl Driver {
     }
 
     pub fn get_doc(location: &StoreLocation) -> Result<Documentation> {
-        let path = location.to_filepath();
-        store::deserialize_object(path)
+        store::read_archive_record(location)
+    }
+
+    /// Adds the methods of a type's `Deref` target to its related items, so they can be shown
+    /// as "Methods from Deref<Target = ...>". The target may live in another crate.
+    pub fn add_deref_methods(store: &Store, doc: &mut Documentation) {
+        let target = match doc.deref_target() {
+            Some(target) => target,
+            None         => return,
+        };
+
+        let methods = store.lookup_type(&target)
+            .and_then(|location| Driver::get_doc(location).ok())
+            .and_then(|target_doc| target_doc.links.get(&DocType::Function).cloned());
+
+        if let Some(methods) = methods {
+            doc.links.insert(DocType::DerefMethod, methods);
+        }
     }
 }
@@ -22,8 +22,40 @@ error_chain! {
             description("crate could not be parsed")
             display("Failed to parse crate {}: {}", krate, err)
         }
+        UnknownFormat(path: String) {
+            description("file was not written by oxidoc")
+            display("{} was not written by this version of oxidoc. Run oxidoc --generate to \
+                     rebuild the documentation.", path)
+        }
+        IncompatibleFormat(path: String, found: u32, expected: u32) {
+            description("file was written by an incompatible version of oxidoc")
+            display("{} was built by an incompatible version of oxidoc (format version {}, \
+                     expected {}). Run oxidoc --generate to rebuild the documentation.",
+                    path, found, expected)
+        }
+        IncompatibleStore(path: String, crates: Vec<String>) {
+            description("documentation store was written by an incompatible version of oxidoc")
+            display("{} was built by an incompatible version of oxidoc. Run oxidoc doctor --repair \
+                     to replace it with an empty store, which drops the documentation of {}, and \
+                     generate it again.",
+                    path,
+                    if crates.is_empty() { "no crates".to_string() } else { crates.join(", ") })
+        }
+        ArchiveChanged(path: String) {
+            description("documentation was regenerated while reading it")
+            display("{} was regenerated while it was being read. Run the query again.", path)
+        }
+        NoSuchCrate(krate: String) {
+            description("crate not found in documentation store")
+            display("No documentation for {} was found in the store.", krate)
+        }
+        ForeignFiles(path: String) {
+            description("directory contains files which weren't written by oxidoc")
+            display("{} contains files which weren't written by oxidoc", path)
+        }
         NameEncodingError(name: String) {
-            description("Failed to encode name: {}")
+            description("name could not be encoded")
+            display("Failed to encode name for a file name: {}", name)
         }
     }
 }
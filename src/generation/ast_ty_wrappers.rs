@@ -66,12 +66,15 @@ impl<I: IntoIterator<Item=ast::NestedMetaItem>> NestedAttributesExt for I {
 #[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Attributes {
     pub doc_strings: Vec<String>,
+    /// Whether the item was marked `#[doc(hidden)]`.
+    pub hidden: bool,
 }
 
 impl Attributes {
     pub fn new() -> Attributes {
         Attributes {
             doc_strings: Vec::new(),
+            hidden: false,
         }
     }
 
@@ -95,6 +98,7 @@ impl Attributes {
         }).collect();
         Attributes {
             doc_strings: doc_strings,
+            hidden: attrs.lists("doc").has_word("hidden"),
             //other_attrs: other_attrs,
         }
     }
@@ -104,6 +108,24 @@ impl Attributes {
     pub fn doc_value<'a>(&'a self) -> Option<&'a str> {
         self.doc_strings.first().map(|s| &s[..])
     }
+
+    /// The first paragraph of the documentation on a single line, like the summaries rustdoc
+    /// shows in module listings.
+    pub fn summary(&self) -> String {
+        let mut words = Vec::new();
+
+        for line in self.doc_strings.iter().flat_map(|s| s.lines()) {
+            if line.trim().is_empty() {
+                if words.is_empty() {
+                    continue;
+                }
+                break;
+            }
+            words.extend(line.split_whitespace());
+        }
+
+        words.join(" ")
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -149,6 +171,8 @@ pub struct Module {
     pub impls: Vec<Impl>,
     pub traits: Vec<Trait>,
     pub def_traits: Vec<DefaultImpl>,
+    /// Primitive types with inherent impls in this module, like `impl str`.
+    pub primitives: Vec<Primitive>,
     pub is_crate: bool,
     pub attrs: Vec<ast::Attribute>,
     pub path: ModPath,
@@ -173,6 +197,7 @@ impl Module {
             impls:      Vec::new(),
             traits:     Vec::new(),
             def_traits: Vec::new(),
+            primitives: Vec::new(),
             is_crate:   false,
             path:       ModPath::new(),
             namespaces_to_paths: HashMap::new(),
@@ -211,6 +236,8 @@ pub struct Trait {
 pub struct TraitItem {
     pub ident: ast::Ident,
     pub attrs: Vec<ast::Attribute>,
+    /// The visibility of the trait this item belongs to.
+    pub vis: ast::Visibility,
     pub path: ModPath,
     pub node: ast::TraitItemKind,
 }
@@ -233,7 +260,7 @@ pub struct Variant {
 
 #[derive(Clone, Debug)]
 pub struct Constant {
-    pub type_: Ty,
+    pub type_: ast::Ty,
     pub expr: ast::Expr,
     pub ident: ast::Ident,
     pub vis: ast::Visibility,
@@ -244,7 +271,7 @@ pub struct Constant {
 #[derive(Clone, Debug)]
 pub struct Impl {
     pub unsafety: ast::Unsafety,
-    //pub generics: ast::Generics,
+    pub generics: ast::Generics,
     pub trait_: Option<ast::TraitRef>,
     pub for_: ast::Ty,
     pub items: Vec<ast::ImplItem>,
@@ -252,6 +279,15 @@ pub struct Impl {
     pub path: ModPath,
 }
 
+/// A primitive type like `str` or `[T]`, documented because the crate has an inherent impl on
+/// it. It's placed in the `primitive` namespace of the crate, like `alloc::primitive::str` or
+/// `alloc::primitive::slice`.
+#[derive(Clone, Debug)]
+pub struct Primitive {
+    pub name: String,
+    pub path: ModPath,
+}
+
 #[derive(Clone, Debug)]
 pub struct DefaultImpl {
     pub unsafety: ast::Unsafety,
@@ -287,15 +323,111 @@ impl From<ast::NodeId> for NodeId {
 #[derive(Hash, Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct Ty {
     pub id: NodeId,
+    /// The type as it was written in the source.
     pub name: String,
+    pub kind: TyKind,
 }
 
-impl From<ast::Ty> for Ty {
-    fn from(ty: ast::Ty) -> Self{
-        Ty {
-            id: NodeId::from(ty.id),
-            name: pprust::ty_to_string(&ty),
+impl Ty {
+    /// The full paths of all types inside this one that were resolved to items in the crate.
+    pub fn resolved_paths(&self) -> Vec<ModPath> {
+        let mut paths = Vec::new();
+        self.kind.collect_resolved_paths(&mut paths);
+        paths
+    }
+}
+
+/// A serializable version of `ast::TyKind`, keeping the parts that are useful for linking and
+/// searching types.
+#[derive(Hash, Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub enum TyKind {
+    /// A path like `Vec<T>` or `io::Result<()>`.
+    Path(TyPath),
+    /// `&'a T` or `&mut T`, with the lifetime if one was given.
+    Ref(Option<String>, Mutability, Box<Ty>),
+    /// `*const T` or `*mut T`
+    Ptr(Mutability, Box<Ty>),
+    /// `[T]`
+    Slice(Box<Ty>),
+    /// `[T; N]`, with the length expression as it was written.
+    Array(Box<Ty>, String),
+    /// `(A, B)`, or `()` if empty.
+    Tuple(Vec<Ty>),
+    /// `fn(A, B) -> C`
+    BareFn(Vec<Ty>, Option<Box<Ty>>),
+    /// `Trait + Send`
+    TraitObject(Vec<TyPath>),
+    /// `impl Trait`
+    ImplTrait(Vec<TyPath>),
+    /// `!`
+    Never,
+    /// `_`
+    Infer,
+    /// The type of `self` in `&self` and `self` arguments.
+    ImplicitSelf,
+    /// Anything else, like macros in type position.
+    Other,
+}
+
+impl TyKind {
+    fn collect_resolved_paths(&self, paths: &mut Vec<ModPath>) {
+        match *self {
+            TyKind::Path(ref path) => path.collect_resolved_paths(paths),
+            TyKind::Ref(_, _, ref ty) |
+            TyKind::Ptr(_, ref ty) |
+            TyKind::Slice(ref ty) |
+            TyKind::Array(ref ty, _) => ty.kind.collect_resolved_paths(paths),
+            TyKind::Tuple(ref tys) => {
+                for ty in tys {
+                    ty.kind.collect_resolved_paths(paths);
+                }
+            },
+            TyKind::BareFn(ref args, ref ret) => {
+                for ty in args.iter().chain(ret.iter().map(|r| &**r)) {
+                    ty.kind.collect_resolved_paths(paths);
+                }
+            },
+            TyKind::TraitObject(ref bounds) |
+            TyKind::ImplTrait(ref bounds) => {
+                for bound in bounds {
+                    bound.collect_resolved_paths(paths);
+                }
+            },
+            TyKind::Never |
+            TyKind::Infer |
+            TyKind::ImplicitSelf |
+            TyKind::Other => (),
+        }
+    }
+}
+
+/// A path to a type or trait, along with its generic arguments.
+#[derive(Hash, Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct TyPath {
+    /// The path as it was written, without generic arguments.
+    pub path: ModPath,
+    /// The full path of the item within the crate, if it could be resolved.
+    pub resolved: Option<ModPath>,
+    /// Type arguments, like `T` in `Vec<T>`, or the arguments of `Fn(A, B)`.
+    pub args: Vec<Ty>,
+    /// Associated type bindings, like `Item = T` in `Iterator<Item = T>`, or the return type of
+    /// `Fn(A) -> B` as `Output`.
+    pub bindings: Vec<(String, Ty)>,
+}
+
+impl TyPath {
+    fn collect_resolved_paths(&self, paths: &mut Vec<ModPath>) {
+        if let Some(ref resolved) = self.resolved {
+            paths.push(resolved.clone());
+        }
+        for ty in self.args.iter().chain(self.bindings.iter().map(|&(_, ref ty)| ty)) {
+            ty.kind.collect_resolved_paths(paths);
         }
     }
 }
 
+#[derive(Hash, Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub enum Mutability {
+    Mutable,
+    Immutable,
+}
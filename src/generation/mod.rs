@@ -5,9 +5,9 @@ pub mod visitor;
 use std;
 use std::env;
 use std::path::{Path, PathBuf};
-use std::fs::{read_dir, remove_dir_all};
+use std::fs::read_dir;
 
-use store::Store;
+use store::{Store, StoreLock};
 use syntax::ast;
 use syntax::diagnostics::plugin::DiagnosticBuilder;
 use syntax::parse::{self, ParseSess};
@@ -16,12 +16,19 @@ use syntax::codemap::FilePathMapping;
 use document::*;
 use conversion::{Convert, Context, Documentation};
 use paths;
-use store::Docset;
 use toml_util;
 use self::visitor::OxidocVisitor;
 
 use ::errors::*;
 
+/// Options that control which items are indexed when generating documentation.
+#[derive(Clone, Debug, Default)]
+pub struct GenerationOptions {
+    /// Also index private and `#[doc(hidden)]` items, like rustdoc's
+    /// `--document-private-items`. Useful for crates in your own workspace.
+    pub document_private_items: bool,
+}
+
 fn parse_crate_from_path<'a, T: ?Sized + AsRef<Path>>(path: &T,
                                                       parse_session: &'a ParseSess)
                                                       -> std::result::Result<ast::Crate, Option<DiagnosticBuilder<'a>>> {
@@ -36,11 +43,11 @@ fn parse_crate_from_path<'a, T: ?Sized + AsRef<Path>>(path: &T,
     }
 }
 
-pub fn generate_all_docs() -> Result<()> {
+pub fn generate_all_docs(options: &GenerationOptions) -> Result<()> {
     debug!("Regenerating all documentation.");
-    generate_crate_registry_docs()?;
+    generate_crate_registry_docs(options)?;
 
-    if generate_stdlib_docs().is_err() {
+    if generate_stdlib_docs(options).is_err() {
         println!("The environment variable RUST_SRC_PATH was not set or malformed. Documentation \
                   for std won't be generated.");
     }
@@ -48,20 +55,23 @@ pub fn generate_all_docs() -> Result<()> {
     Ok(())
 }
 
-pub fn generate_crate_registry_docs() -> Result<()> {
-    let path = paths::doc_registry_path()?;
-
-    remove_dir_all(path);
+pub fn generate_crate_registry_docs(options: &GenerationOptions) -> Result<()> {
+    {
+        // Released before generating, which takes the lock for every crate.
+        let _lock = StoreLock::acquire()?;
+        Store::load_for_generation()?.clear()
+            .chain_err(|| "Could not remove the existing documentation")?;
+    }
 
     for src_dir in paths::iter_crate_source_paths()
         .chain_err(|| "Could not iterate cargo registry src directories")?
     {
-        generate_docs_for_path(src_dir)?;
+        generate_docs_for_path(src_dir, options)?;
     }
     Ok(())
 }
 
-pub fn generate_stdlib_docs() -> Result<()> {
+pub fn generate_stdlib_docs(options: &GenerationOptions) -> Result<()> {
     let rust_src_dir = env::var("RUST_SRC_PATH")
         .chain_err(|| format!("RUST_SRC_PATH was not set when trying to generate stdlib docs."))?;
 
@@ -86,14 +96,14 @@ pub fn generate_stdlib_docs() -> Result<()> {
     for path in paths {
         // BUG: ICE when attempting to parse rustdoc. Just skip parsing librustdoc.
         if !path.display().to_string().contains("librustdoc") {
-            generate_docs_for_path(path)?;
+            generate_docs_for_path(path, options)?;
         }
     }
     Ok(())
 }
 
-pub fn generate_docs_for_path(src_dir: PathBuf) -> Result<()> {
-    cache_doc_for_crate(&src_dir).
+pub fn generate_docs_for_path(src_dir: PathBuf, options: &GenerationOptions) -> Result<()> {
+    cache_doc_for_crate(&src_dir, options).
         chain_err(|| format!("Unable to generate documentation \
                               for directory {}",
                              &src_dir.display()))?;
@@ -117,7 +127,7 @@ fn get_crate_info(crate_path: &PathBuf) -> Result<CrateInfo> {
 
 /// Generates cached Rustdoc information for the given crate.
 /// Expects the crate root directory as an argument.
-fn cache_doc_for_crate(crate_path: &PathBuf) -> Result<()> {
+fn cache_doc_for_crate(crate_path: &PathBuf, options: &GenerationOptions) -> Result<()> {
     let info = get_crate_info(crate_path)?;
 
     println!("Generating documentation for {}", &info);
@@ -131,11 +141,10 @@ fn cache_doc_for_crate(crate_path: &PathBuf) -> Result<()> {
         }
     };
 
-    let mut store = generate_doc_cache(krate, info)
+    generate_doc_cache(krate, info, options)
         .chain_err(|| "Failed to generate doc cache")?;
 
-    store.save()
-        .chain_err(|| "Couldn't save oxidoc data for module")
+    Ok(())
 }
 
 fn parse_crate(crate_path: &PathBuf, crate_info: &CrateInfo) -> Result<ast::Crate> {
@@ -160,39 +169,28 @@ fn parse_crate(crate_path: &PathBuf, crate_info: &CrateInfo) -> Result<ast::Crat
     Ok(krate)
 }
 
-pub fn generate_crate_docs(krate: ast::Crate, crate_info: CrateInfo) -> Result<Vec<Documentation>> {
-    let crate_doc_path = paths::crate_doc_path(&crate_info)
-        .chain_err(|| format!("Unable to get crate doc path for crate: {}",
-                              &crate_info.name))?;
-
-    let mut v = OxidocVisitor::new(crate_info.clone());
+pub fn generate_crate_docs(krate: ast::Crate,
+                           crate_info: CrateInfo,
+                           options: &GenerationOptions) -> Result<Vec<Documentation>> {
+    let mut v = OxidocVisitor::new(crate_info.clone(), options.clone());
     v.visit_crate(krate);
-    let context = Context::new(crate_doc_path.clone(),
-                               crate_info,
-                               v.impls_for_ty.clone());
+    let context = Context::new(crate_info, v.impls_for_ty.clone());
     Ok(v.convert(&context))
 }
 
-pub fn make_docset(documents: Vec<Documentation>) -> Result<Docset> {
-    for doc in &documents {
-        debug!("p: {}", doc.mod_path);
-        doc.save()?;
-    }
-
-    let mut docset = Docset::new();
-    docset.add_docs(documents)?;
-
-    Ok(docset)
-}
-
 /// Generates documentation for the given crate.
-pub fn generate_doc_cache(krate: ast::Crate, crate_info: CrateInfo) -> Result<Store> {
-    let documents = generate_crate_docs(krate, crate_info.clone())?;
-    let docset = make_docset(documents)?;
+pub fn generate_doc_cache(krate: ast::Crate,
+                          crate_info: CrateInfo,
+                          options: &GenerationOptions) -> Result<Store> {
+    let documents = generate_crate_docs(krate, crate_info.clone(), options)?;
+
+    // Other processes may be generating documentation at the same time.
+    let _lock = StoreLock::acquire()?;
 
-    let mut store = Store::load();
-    store.add_docset(crate_info, docset);
-    store.save()?;
+    let mut store = Store::load_for_generation()?;
+    store.add_documents(crate_info, documents)?;
+    store.save()
+        .chain_err(|| "Couldn't save oxidoc data for module")?;
 
     Ok(store)
 }
@@ -6,6 +6,7 @@ use syntax::print::pprust;
 use syntax::symbol::keywords;
 
 use document::*;
+use generation::GenerationOptions;
 use generation::ast_ty_wrappers::*;
 
 /// Visits the AST starting at a crate and creates a tree of documentation
@@ -21,31 +22,39 @@ pub struct OxidocVisitor {
     pub crate_module: Module,
     pub name_for_ty: HashMap<NodeId, ast::Ident>,
     pub impls_for_ty: HashMap<ModPath, Vec<Impl>>,
+    /// Whether each module, type and trait in the crate can be used from
+    /// outside of it. Filled in before any impls are added.
+    pub reachable_items: HashMap<ModPath, bool>,
+    pub options: GenerationOptions,
 }
 
 impl OxidocVisitor {
-    pub fn new(crate_info: CrateInfo) -> OxidocVisitor {
+    pub fn new(crate_info: CrateInfo, options: GenerationOptions) -> OxidocVisitor {
         OxidocVisitor {
             crate_module: Module::new(None),
             current_scope: ModPath::new(),
             crate_info: crate_info,
             name_for_ty: HashMap::new(),
             impls_for_ty: HashMap::new(),
+            reachable_items: HashMap::new(),
+            options: options,
         }
     }
 
-    fn visit_impl_const(&self, item: &ast::ImplItem, for_path: &ModPath, ty: &ast::Ty, expr: &ast::Expr) -> Constant {
+    fn visit_impl_const(&self, item: &ast::ImplItem, for_path: &ModPath, vis: ast::Visibility,
+                        ty: &ast::Ty, expr: &ast::Expr) -> Constant {
         Constant {
             ident: item.ident,
-            type_: Ty::from(ty.clone()),
+            type_: ty.clone(),
             expr: expr.clone(),
-            vis: item.vis.clone(),
+            vis: vis,
             attrs: item.attrs.clone(),
             path: for_path.append_ident(item.ident),
         }
     }
 
-    fn visit_impl_method(&self, item: &ast::ImplItem, for_path: &ModPath, sig: &ast::MethodSig) -> Function {
+    fn visit_impl_method(&self, item: &ast::ImplItem, for_path: &ModPath, vis: ast::Visibility,
+                         sig: &ast::MethodSig) -> Function {
         // In this case, the final segment of the ModPath is used as the type
         // the item is implemented on.
         Function {
@@ -53,7 +62,7 @@ impl OxidocVisitor {
             decl: (*sig.decl).clone(),
             unsafety: sig.unsafety.clone(),
             constness: sig.constness.node.clone(),
-            vis: item.vis.clone(),
+            vis: vis,
             abi: sig.abi.clone(),
             attrs: item.attrs.clone(),
             kind: FnKind::MethodFromImpl,
@@ -61,14 +70,27 @@ impl OxidocVisitor {
         }
     }
 
-    fn visit_impl_item(&self, module: &mut Module, item: &ast::ImplItem, for_path: &ModPath) {
+    fn visit_impl_item(&self, module: &mut Module, item: &ast::ImplItem, for_path: &ModPath,
+                       is_trait_impl: bool, is_reachable: bool) {
+        // Items of trait impls have inherited visibility, but are public
+        // through the trait if both the trait and the type are reachable.
+        // Items of impls on unreachable types stay private whatever their own
+        // visibility is.
+        let vis = if !is_reachable {
+            ast::Visibility::Inherited
+        } else if is_trait_impl {
+            ast::Visibility::Public
+        } else {
+            item.vis.clone()
+        };
+
         match item.node {
             ast::ImplItemKind::Const(ref ty, ref expr) => {
-                let c = self.visit_impl_const(item, for_path, ty, expr);
+                let c = self.visit_impl_const(item, for_path, vis, ty, expr);
                 module.consts.push(c);
             },
             ast::ImplItemKind::Method(ref sig, _) => {
-                let f = self.visit_impl_method(item, for_path, sig);
+                let f = self.visit_impl_method(item, for_path, vis, sig);
                 module.fns.push(f);
             },
             // TODO: Handle types and macros
@@ -77,17 +99,149 @@ impl OxidocVisitor {
         }
     }
 
-    fn add_impl(&mut self, module: &mut Module, imp: Impl) {
-        if let ast::TyKind::Path(_, path) = imp.for_.node.clone() {
-            let namespaced_path = ModPath::from(path.clone());
-            if let Some(full_path) = module.resolve_use(&namespaced_path) {
-                debug!("Full path for {}: {}", namespaced_path, full_path);
-                for item in &imp.items {
-                    self.visit_impl_item(module, &item, &full_path);
+    fn add_impl(&mut self, module: &mut Module, mut imp: Impl) {
+        let (full_path, primitive) = match self.resolve_impl_target(module, &imp) {
+            Some(target) => target,
+            None         => return,
+        };
+        debug!("Full path for impl: {}", full_path);
+
+        let is_trait_impl = imp.trait_.is_some();
+        let is_reachable = self.is_impl_reachable(module, &imp, &full_path, primitive.is_some());
+
+        if !self.options.document_private_items {
+            if !is_reachable {
+                debug!("Skipping unreachable impl for {}", full_path);
+                return;
+            }
+            imp.items.retain(|item| should_visit_impl_item(item, is_trait_impl));
+        }
+
+        if let Some(name) = primitive {
+            if !imp.items.is_empty() {
+                self.add_primitive(module, name, &full_path);
+            }
+        }
+
+        for item in &imp.items {
+            self.visit_impl_item(module, &item, &full_path, is_trait_impl, is_reachable);
+        }
+        self.impls_for_ty.entry(full_path.clone()).or_insert(Vec::new()).push(imp);
+    }
+
+    /// Finds the full path of the type an impl is for, along with the name of the primitive
+    /// type for inherent impls on primitives like `impl str` or `impl<T> [T]`. These are
+    /// documented under `<crate>::primitive`, so they can't clash with modules named like them.
+    fn resolve_impl_target(&self, module: &Module,
+                           imp: &Impl) -> Option<(ModPath, Option<String>)> {
+        let primitive = match imp.for_.node {
+            ast::TyKind::Path(_, ref path) => {
+                let namespaced_path = ModPath::from(path.clone());
+                if let Some(full_path) = module.resolve_use(&namespaced_path) {
+                    return Some((full_path, None));
+                }
+
+                let name = namespaced_path.to_string();
+                if imp.trait_.is_some() || !is_primitive_name(&name) {
+                    debug!("No type found for impl {}", namespaced_path);
+                    return None;
+                }
+                name
+            },
+            ast::TyKind::Slice(..) if imp.trait_.is_none() => "slice".to_string(),
+            _ => return None,
+        };
+
+        let mut path = ModPath::from(self.crate_info.name.clone());
+        path.push_string("primitive".to_string());
+        path.push_string(primitive.clone());
+
+        Some((path, Some(primitive)))
+    }
+
+    /// Documents a primitive type the first time an impl on it is kept.
+    fn add_primitive(&self, module: &mut Module, name: String, path: &ModPath) {
+        let is_known = self.impls_for_ty.contains_key(path) ||
+            module.primitives.iter().any(|p| p.path == *path);
+        if !is_known {
+            module.primitives.push(Primitive {
+                name: name,
+                path: path.clone(),
+            });
+        }
+    }
+
+    /// Whether the items of an impl can be used from outside the crate. The
+    /// implementing type has to be reachable, unless it's a primitive, and so
+    /// does the trait for trait impls.
+    fn is_impl_reachable(&self, module: &Module, imp: &Impl, for_path: &ModPath,
+                         is_primitive: bool) -> bool {
+        if !is_primitive && !self.is_reachable(for_path) {
+            return false;
+        }
+
+        match imp.trait_ {
+            Some(ref trait_ref) => {
+                let trait_path = ModPath::from(trait_ref.path.clone());
+                match module.resolve_use(&trait_path) {
+                    Some(full_trait_path) => self.is_reachable(&full_trait_path),
+                    // Traits that weren't imported are either defined in
+                    // this module or come from the prelude.
+                    None => {
+                        let local_path = ModPath::join(&module.path, &trait_path);
+                        *self.reachable_items.get(&local_path).unwrap_or(&true)
+                    },
                 }
-                self.impls_for_ty.entry(full_path.clone()).or_insert(Vec::new()).push(imp);
-            } else {
-                debug!("No type found for impl {}", namespaced_path);
+            },
+            None => true,
+        }
+    }
+
+    /// Whether the item at `path` is public, not hidden and inside public
+    /// modules. Paths from `use` declarations may be relative to the crate
+    /// root. Unknown paths inside this crate are unreachable, while paths
+    /// into other crates are assumed to be reachable.
+    fn is_reachable(&self, path: &ModPath) -> bool {
+        let crate_root = ModPath::from(self.crate_info.name.clone());
+        let rooted_path = ModPath::join(&crate_root, path);
+        if let Some(reachable) = self.reachable_items.get(path)
+            .or_else(|| self.reachable_items.get(&rooted_path)) {
+            return *reachable;
+        }
+
+        match path.head() {
+            Some(head) => {
+                let is_crate_name = head.identifier == self.crate_info.name;
+                let mut root_item = crate_root.clone();
+                root_item.push_string(head.identifier);
+                !is_crate_name && !self.reachable_items.contains_key(&root_item)
+            },
+            None => false,
+        }
+    }
+
+    /// Records whether every module, type and trait in `m` is reachable before
+    /// any impls are added, so impls can be filtered no matter where they
+    /// appear relative to the items they are for.
+    fn record_reachability(&mut self, m: &ast::Mod, scope: &ModPath, is_scope_reachable: bool) {
+        for item in &m.items {
+            let is_hidden = item.attrs.lists("doc").has_word("hidden");
+            let reachable = is_scope_reachable && item.vis == ast::Visibility::Public && !is_hidden;
+            let path = scope.append_ident(item.ident);
+
+            match item.node {
+                ast::ItemKind::Mod(ref mod_) => {
+                    self.record_reachability(mod_, &path, reachable);
+                    self.reachable_items.insert(path, reachable);
+                },
+                ast::ItemKind::Struct(..) |
+                ast::ItemKind::Enum(..) |
+                ast::ItemKind::Union(..) |
+                ast::ItemKind::Trait(..) |
+                ast::ItemKind::Ty(..) => {
+                    self.reachable_items.insert(path, reachable);
+                },
+                _ => (),
             }
         }
     }
@@ -129,7 +283,7 @@ impl OxidocVisitor {
     ) -> Constant {
         Constant {
             ident: item.ident,
-            type_: Ty::from(ast_ty.clone()),
+            type_: ast_ty.clone(),
             expr:  ast_expr.clone(),
             vis: item.vis.clone(),
             attrs: item.attrs.clone(),
@@ -160,6 +314,7 @@ impl OxidocVisitor {
                 TraitItem {
                     ident: ti.ident,
                     attrs: ti.attrs.clone(),
+                    vis: item.vis.clone(),
                     path: ModPath::join(&self.current_scope.append_ident(item.ident),
                                         &ModPath::from(ti.ident)),
                     node: ti.node,
@@ -176,12 +331,13 @@ impl OxidocVisitor {
     fn visit_impl(&self, item: &ast::Item,
                   ast_unsafety: ast::Unsafety,
                   _ast_defaultness: &ast::Defaultness,
-                  _ast_generics: &ast::Generics,
+                  ast_generics: &ast::Generics,
                   ast_trait_ref: &Option<ast::TraitRef>,
                   ast_ty: &ast::Ty,
                   items: &Vec<ast::ImplItem>) -> Impl {
         Impl {
             unsafety: ast_unsafety,
+            generics: ast_generics.clone(),
             trait_: ast_trait_ref.clone(),
             for_: ast_ty.clone(),
             items: items.clone(),
@@ -266,8 +422,9 @@ impl OxidocVisitor {
                 module.fns.push(f);
             },
             ast::ItemKind::Mod(ref mod_) => {
-                let m = self.visit_module(item.attrs.clone(),
-                                          mod_, Some(item.ident));
+                let mut m = self.visit_module(item.attrs.clone(),
+                                              mod_, Some(item.ident));
+                m.vis = item.vis.clone();
                 module.mods.push(m);
             },
             ast::ItemKind::Enum(ref def, ref generics) => {
@@ -291,6 +448,7 @@ impl OxidocVisitor {
                 let t = self.visit_trait(item,
                                          unsafety, generics,
                                          trait_items);
+                module.add_use(&item.ident, t.path.clone());
                 module.traits.push(t);
             },
             ast::ItemKind::DefaultImpl(unsafety, ref trait_ref) => {
@@ -327,7 +485,7 @@ impl OxidocVisitor {
         module.path = self.current_scope.clone();
 
         for item in &m.items {
-            if should_visit_item(&item) {
+            if should_visit_item(&item, self.options.document_private_items) {
                 self.visit_item(item, &mut module);
             }
         }
@@ -343,14 +501,21 @@ impl OxidocVisitor {
 
     pub fn visit_crate(&mut self, krate: ast::Crate) {
         debug!("visiting crate");
+        let crate_root = ModPath::from(self.crate_info.name.clone());
+        self.record_reachability(&krate.module, &crate_root, true);
         self.crate_module = self.visit_module(krate.attrs.clone(),
                                               &krate.module,
                                               None);
+        self.crate_module.vis = ast::Visibility::Public;
         self.crate_module.is_crate = true;
     }
 }
 
-fn should_visit_item(item: &ast::Item) -> bool {
+fn should_visit_item(item: &ast::Item, document_private_items: bool) -> bool {
+    if document_private_items {
+        return true;
+    }
+
     // TODO: Until "pub use" works, public reexports may not be visited, so just visit all modules
     // to find them.
     let is_module = match item.node {
@@ -360,7 +525,7 @@ fn should_visit_item(item: &ast::Item) -> bool {
 
     let is_hidden = item.attrs.lists("doc").has_word("hidden");
 
-    // methods in impls inherit the visibility of the parent
+    // Impls are always visited; their items are filtered in `add_impl`.
     let is_public = match item.node {
         ast::ItemKind::Impl(..) => true,
         _ => item.vis == ast::Visibility::Public,
@@ -369,6 +534,25 @@ fn should_visit_item(item: &ast::Item) -> bool {
     !is_hidden && (is_module || is_public)
 }
 
+fn should_visit_impl_item(item: &ast::ImplItem, is_trait_impl: bool) -> bool {
+    let is_hidden = item.attrs.lists("doc").has_word("hidden");
+
+    // Trait impl items are as visible as the trait and type themselves.
+    let is_public = is_trait_impl || item.vis == ast::Visibility::Public;
+
+    !is_hidden && is_public
+}
+
+/// Whether `name` is a primitive type which can have inherent impls.
+fn is_primitive_name(name: &str) -> bool {
+    const PRIMITIVES: &'static [&'static str] = &[
+        "bool", "char", "str", "f32", "f64",
+        "i8", "i16", "i32", "i64", "i128", "isize",
+        "u8", "u16", "u32", "u64", "u128", "usize",
+    ];
+    PRIMITIVES.contains(&name)
+}
+
 fn current_module_scope(visitor: &OxidocVisitor, mod_name: Option<ast::Ident>) -> String {
     if let Some(name) = mod_name {
         pprust::ident_to_string(name)
@@ -7,12 +7,16 @@ extern crate log;
 #[macro_use]
 extern crate serde_derive;
 
+#[macro_use]
 extern crate clap;
 extern crate ansi_term;
 extern crate bincode;
 extern crate cursive;
 extern crate env_logger;
+extern crate flate2;
+extern crate libc;
 extern crate regex;
+extern crate semver;
 extern crate serde;
 extern crate strsim;
 extern crate syntex_syntax as syntax;
@@ -23,6 +27,9 @@ extern crate catmark;
 #[cfg(unix)]
 extern crate pager;
 
+pub mod backend;
+pub mod bundle;
+pub mod cli;
 pub mod conversion;
 pub mod generation;
 pub mod document;
@@ -32,4 +39,9 @@ pub mod store;
 mod toml_util;
 pub mod tui;
 pub mod errors;
-mod paths;
+pub mod path_index;
+pub mod paths;
+pub mod ranking;
+pub mod search;
+pub mod text_search;
+pub mod type_search;
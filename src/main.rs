@@ -19,46 +19,28 @@ extern crate oxidoc;
 
 use std::path::PathBuf;
 
-use clap::{App, Arg};
+use ansi_term::Style;
+use clap::ArgMatches;
+use oxidoc::bundle::Bundle;
+use oxidoc::cli;
+use oxidoc::document::ModPath;
 use oxidoc::driver::Driver;
-use oxidoc::generation;
+use oxidoc::generation::{self, GenerationOptions};
+use oxidoc::paths;
+use oxidoc::search::{ItemKind, SearchFilter};
+use oxidoc::text_search;
 use oxidoc::errors::*;
 use oxidoc::store::StoreLocation;
-use oxidoc::markup::Format;
-use oxidoc::store::Store;
+use oxidoc::markup::{self, Format, FormatOptions};
+use oxidoc::store::{Store, StoreLock};
 
 #[cfg(unix)]
 extern crate pager;
 #[cfg(unix)]
 use pager::Pager;
 
-fn app<'a, 'b>() -> App<'a, 'b> {
-    App::new(format!("oxidoc {}", crate_version!()))
-        .about("A command line interface to Rustdoc.")
-        .arg(Arg::with_name("version").short("V").long("version").help(
-            "Prints version info",
-        ))
-        .arg(Arg::with_name("tui").short("t").long("tui").help(
-            "Starts interactive console user interface",
-        ))
-        .arg(
-            Arg::with_name("generate")
-                .short("g")
-                .long("generate")
-                .value_name("CRATE_DIR")
-                .help(
-                    "Generate oxidoc info for the specified crate root directory, 'std' for stdlib \
-                    (requires RUST_SRC_PATH to be set), 'crates' for all cargo crates or 'all' \
-                    for everything",
-                )
-                .takes_value(true)
-                .alias("generate"),
-        )
-        .arg(Arg::with_name("pager").short("p").long("pager").help(
-            "Automatically pages output",
-        ))
-        .arg(Arg::with_name("query").index(1))
-}
+/// The maximum length of summaries in lists of results.
+const SUMMARY_WIDTH: usize = 100;
 
 fn main() {
     env_logger::init().unwrap();
@@ -78,25 +60,57 @@ fn main() {
     }
 }
 
-fn generate(arg: Option<&str>) -> Result<()> {
+fn generate(arg: Option<&str>, options: &GenerationOptions) -> Result<()> {
     match arg {
-        Some("all") => generation::generate_all_docs(),
-        Some("crates") => generation::generate_crate_registry_docs(),
-        Some("std") => generation::generate_stdlib_docs(),
-        Some(x) => generation::generate_docs_for_path(PathBuf::from(x)),
+        Some("all") => generation::generate_all_docs(options),
+        Some("crates") => generation::generate_crate_registry_docs(options),
+        Some("std") => generation::generate_stdlib_docs(options),
+        Some(x) => generation::generate_docs_for_path(PathBuf::from(x), options),
         None => bail!(ErrorKind::NoCrateDirectoryProvided),
     }
 }
 
 fn run() -> Result<()> {
-    let matches = app().get_matches();
+    let matches = cli::app().get_matches();
     if matches.is_present("version") {
         println!("oxidoc {}", crate_version!());
         return Ok(());
     }
 
+    if let Some(dir) = matches.value_of("store") {
+        paths::set_store_root(PathBuf::from(dir));
+    }
+
     if matches.is_present("generate") {
-        return generate(matches.value_of("generate"));
+        let options = GenerationOptions {
+            document_private_items: matches.is_present("document-private-items"),
+        };
+        return generate(matches.value_of("generate"), &options);
+    }
+
+    match matches.subcommand() {
+        ("remove", Some(sub_matches)) => {
+            let keep = match sub_matches.value_of("keep") {
+                Some(n) => Some(n.parse::<usize>()
+                                .chain_err(|| format!("Invalid --keep count {}", n))?),
+                None    => None,
+            };
+            return remove(sub_matches.value_of("crate"), keep);
+        },
+        ("export", Some(sub_matches)) => {
+            return export(sub_matches.value_of("crate").unwrap(), sub_matches.value_of("output"));
+        },
+        ("import", Some(sub_matches)) => return import(sub_matches.value_of("file").unwrap()),
+        ("doctor", Some(sub_matches)) => return doctor(sub_matches.is_present("repair")),
+        ("list", Some(sub_matches)) => return list(sub_matches.value_of("crate")),
+        ("tree", Some(sub_matches)) => {
+            return tree(sub_matches.value_of("path").unwrap(), sub_matches.is_present("private"));
+        },
+        _ => (),
+    }
+
+    if let Some(prefix) = matches.value_of("complete") {
+        return complete(prefix);
     }
 
     if matches.is_present("tui") {
@@ -108,8 +122,299 @@ fn run() -> Result<()> {
         };
 
         let enable_pager = matches.is_present("pager");
-        print_search_query(query, enable_pager)
+        let brief = matches.is_present("brief");
+        let format_options = FormatOptions {
+            show_source: matches.is_present("source"),
+            highlight: markup::stdout_is_terminal(),
+        };
+
+        let mut filter = search_filter(&matches);
+        let query = filter.parse_prefixes(query);
+
+        retry_if_regenerated(|| {
+            if matches.is_present("text") {
+                print_text_search(query, enable_pager, &filter)
+            } else if matches.is_present("signature") {
+                print_signature_search(query, enable_pager, &filter)
+            } else {
+                print_search_query(query, enable_pager, brief, &filter, &format_options)
+            }
+        })
+    }
+}
+
+/// How often a query is attempted when documentation keeps being regenerated while reading it.
+const MAX_QUERY_ATTEMPTS: usize = 3;
+
+/// Runs a query again if an archive was regenerated after the store was loaded. Queries load the
+/// store themselves, so the retry sees the new index. They have to load all documents before
+/// printing anything, so that nothing is printed twice.
+fn retry_if_regenerated<F>(mut query: F) -> Result<()>
+    where F: FnMut() -> Result<()>
+{
+    let mut attempts = 1;
+    loop {
+        match query() {
+            Err(Error(ErrorKind::ArchiveChanged(ref source), _)) if attempts < MAX_QUERY_ATTEMPTS => {
+                debug!("{} was regenerated, reloading the store.", source);
+                attempts += 1;
+            },
+            result => return result,
+        }
+    }
+}
+
+/// Builds the filter for search results from the command line flags.
+fn search_filter(matches: &ArgMatches) -> SearchFilter {
+    let mut filter = SearchFilter::new();
+
+    if let Some(kinds) = matches.values_of("kind") {
+        filter.kinds = kinds.filter_map(ItemKind::parse).collect();
+    }
+    if let Some(spec) = matches.value_of("crate") {
+        let (name, version) = parse_crate_spec(spec);
+        filter.krate = Some(name.to_string());
+        filter.version = version.map(|v| v.to_string());
+    }
+    filter.unsafe_only = matches.is_present("unsafe");
+    filter.const_fn_only = matches.is_present("const-fn");
+    filter.include_private = matches.is_present("private");
+
+    filter
+}
+
+/// The maximum number of completions printed by `--complete`.
+const MAX_COMPLETIONS: usize = 50;
+
+fn complete(prefix: &str) -> Result<()> {
+    let store = Store::load_layered()?;
+
+    for completion in store.complete(prefix, MAX_COMPLETIONS) {
+        println!("{}", completion);
+    }
+
+    Ok(())
+}
+
+/// Splits a `crate@version` argument into the crate name and version.
+fn parse_crate_spec(spec: &str) -> (&str, Option<&str>) {
+    let mut parts = spec.splitn(2, '@');
+    let name = parts.next().unwrap();
+    (name, parts.next())
+}
+
+/// Removes the documentation for the crate given by `spec`, or for every crate without one.
+fn remove(spec: Option<&str>, keep: Option<usize>) -> Result<()> {
+    let _lock = StoreLock::acquire()?;
+    let mut store = Store::load()?;
+
+    let (krate_names, version): (Vec<String>, Option<&str>) = match spec {
+        Some(spec) => {
+            let (name, version) = parse_crate_spec(spec);
+            (vec![name.to_string()], version)
+        },
+        None => (store.crate_names().iter().map(|s| s.to_string()).collect(), None),
+    };
+    if version.is_some() && keep.is_some() {
+        bail!("--keep can't be used when removing a single version");
+    }
+
+    let mut removed = Vec::new();
+    for krate in krate_names.iter() {
+        let result = match (version, keep) {
+            (Some(version), _) => store.remove_version(krate, version).map(|c| vec![c]),
+            (None, Some(n))    => store.retain_newest_versions(krate, n),
+            (None, None)       => store.remove_crate(krate),
+        };
+
+        match result {
+            Ok(crates) => removed.extend(crates),
+            Err(e) => {
+                // Documentation files of earlier crates may already be gone.
+                store.save()?;
+                return Err(e);
+            }
+        }
+    }
+
+    store.save()?;
+
+    for crate_info in removed.iter() {
+        println!("Removed {}", crate_info);
+    }
+    println!("Removed {} crate version(s).", removed.len());
+
+    Ok(())
+}
+
+fn export(spec: &str, output: Option<&str>) -> Result<()> {
+    let mut store = Store::load_layered()?;
+    store.load_text_index()?;
+    store.load_signature_index()?;
+
+    let (name, version) = parse_crate_spec(spec);
+    let bundle = store.export_bundle(name, version)?;
+
+    let path = match output {
+        Some(file) => PathBuf::from(file),
+        None       => PathBuf::from(bundle.default_file_name()),
+    };
+    bundle.save(&path)?;
+
+    println!("Exported {} ({} documents) to {}",
+             bundle.crate_info,
+             bundle.docset.documents.len(),
+             path.display());
+
+    Ok(())
+}
+
+fn import(file: &str) -> Result<()> {
+    let bundle = Bundle::load(file)?;
+
+    let _lock = StoreLock::acquire()?;
+    let mut store = Store::load_for_generation()?;
+    let crate_info = store.import_bundle(bundle)
+        .chain_err(|| format!("Could not import {}", file))?;
+    store.save()?;
+
+    println!("Imported {}", crate_info);
+
+    Ok(())
+}
+
+fn list(spec: Option<&str>) -> Result<()> {
+    let store = Store::load_layered()?;
+
+    let spec = match spec {
+        Some(spec) => spec,
+        None => {
+            for name in store.crate_names() {
+                println!("{} {}", name, store.crate_versions(name).join(", "));
+            }
+            return Ok(());
+        }
+    };
+
+    let (name, version) = parse_crate_spec(spec);
+    let versions = match version {
+        Some(version) => {
+            match store.docset(name, Some(version)) {
+                Some((v, _)) => vec![v],
+                None         => vec![],
+            }
+        },
+        None => store.crate_versions(name),
+    };
+
+    if versions.is_empty() {
+        bail!(ErrorKind::NoSuchCrate(spec.to_string()));
+    }
+
+    for version in versions {
+        let (_, docset) = store.docset(name, Some(version)).unwrap();
+        println!("{} {}: {} items", name, version, docset.len());
+        for (doc_type, count) in docset.count_by_type() {
+            println!("    {}: {}", doc_type, count);
+        }
+    }
+
+    Ok(())
+}
+
+fn tree(query: &str, include_private: bool) -> Result<()> {
+    let store = Store::load_layered()?;
+
+    let mut segments = query.splitn(2, "::");
+    let (name, version) = parse_crate_spec(segments.next().unwrap());
+    let path = match segments.next() {
+        Some(rest) => ModPath::from(format!("{}::{}", name, rest)),
+        None       => ModPath::from(name.to_string()),
+    };
+
+    let found_version = match store.docset(name, version) {
+        Some((v, _)) => v,
+        None => bail!(ErrorKind::NoSuchCrate(match version {
+            Some(version) => format!("{}@{}", name, version),
+            None          => name.to_string(),
+        })),
+    };
+
+    let items = store.tree(&path, version, include_private);
+    if items.is_empty() {
+        println!("No items found below {} in {} {}.", path, name, found_version);
+        return Ok(());
+    }
+
+    println!("{} ({} {})", path, name, found_version);
+    for (depth, location) in items {
+        let summary = location.short_summary(SUMMARY_WIDTH);
+        let separator = if summary.is_empty() { "" } else { " - " };
+        println!("{}{} ({}){}{}",
+                 "    ".repeat(depth + 1),
+                 location.name,
+                 location.doc_type.kind_name(),
+                 separator,
+                 summary);
     }
+
+    Ok(())
+}
+
+fn doctor(repair: bool) -> Result<()> {
+    let _lock = StoreLock::acquire()?;
+    let mut store = match Store::load_for_generation() {
+        Err(Error(ErrorKind::IncompatibleStore(..), _)) if repair => {
+            let (mut store, dropped) = Store::replace_incompatible()?;
+            store.save()?;
+            println!("Replaced the documentation store, which was built by an incompatible \
+                      version of oxidoc.");
+            if !dropped.is_empty() {
+                println!("Run oxidoc --generate to restore the documentation of {}.",
+                         dropped.join(", "));
+            }
+            return Ok(());
+        },
+        result => result?,
+    };
+    let report = store.check_integrity()?;
+
+    for location in report.missing.iter() {
+        println!("Missing: {}", location);
+    }
+    for &(ref location, ref error) in report.corrupt.iter() {
+        println!("Corrupt: {}: {}", location, error);
+    }
+    for name in report.orphaned.iter() {
+        println!("Orphaned: {}", name);
+    }
+    for name in report.legacy_dirs.iter() {
+        println!("Possibly left over from an old version of oxidoc: {}", name);
+    }
+
+    println!("Checked {} documents: {} missing, {} corrupt, {} orphaned.",
+             report.checked,
+             report.missing.len(),
+             report.corrupt.len(),
+             report.orphaned.len());
+
+    if report.is_ok() && report.legacy_dirs.is_empty() {
+        return Ok(());
+    }
+
+    if repair {
+        let skipped = store.repair(&report)?;
+        store.save()?;
+        for name in skipped.iter() {
+            println!("Skipped {}, it contains files which weren't written by oxidoc.", name);
+        }
+        println!("Repaired the documentation store. Run oxidoc --generate to restore removed \
+                  documentation.");
+    } else {
+        println!("Run oxidoc doctor --repair to remove the broken documentation.");
+    }
+
+    Ok(())
 }
 
 #[cfg(windows)]
@@ -139,33 +444,169 @@ fn get_pager_executable() -> String {
     return executable.to_string();
 }
 
-fn print_search_query(query: &str, enable_pager: bool) -> Result<()> {
-    let store = Store::load();
-    // search::add_search_paths(store.all_locations());
+/// The number of words in the passages shown for full-text search results.
+const SNIPPET_WIDTH: usize = 24;
+
+fn print_text_search(query: &str, enable_pager: bool, filter: &SearchFilter) -> Result<()> {
+    let mut store = Store::load_layered()?;
+    store.load_text_index()?;
 
-    let results: Vec<&StoreLocation> = store.lookup_name(query).into_iter().take(10).collect();
+    let results: Vec<(f64, &StoreLocation)> = store.search_text(query, filter)?
+        .into_iter()
+        .take(10)
+        .collect();
 
     if results.is_empty() {
-        println!("No results for \"{}\".", query);
+        println!("No documentation mentions \"{}\".", query);
         return Ok(());
     }
 
-    let formatted: Vec<String> = results
+    // Only the few results shown are loaded, to show where the query matched.
+    let mut passages = Vec::new();
+    for &(_, location) in results.iter() {
+        let passage = match Driver::get_doc(location) {
+            Ok(doc) => {
+                let text = doc.attrs.doc_strings.join("\n");
+                text_search::snippet(&text, query, SNIPPET_WIDTH, |word| {
+                    Style::new().bold().paint(word).to_string()
+                })
+            },
+            Err(e @ Error(ErrorKind::ArchiveChanged(..), _)) => return Err(e),
+            Err(_) => location.short_summary(SUMMARY_WIDTH),
+        };
+        passages.push(passage);
+    }
+
+    if enable_pager {
+        setup_pager();
+    }
+
+    for ((score, location), passage) in results.into_iter().zip(passages) {
+        println!("{} ({}, score {:.2})", location, location.doc_type.kind_name(), score);
+        println!("    {}\n", passage);
+    }
+
+    Ok(())
+}
+
+fn print_signature_search(query: &str, enable_pager: bool, filter: &SearchFilter) -> Result<()> {
+    let mut store = Store::load_layered()?;
+    store.load_signature_index()?;
+
+    let results: Vec<(u32, &StoreLocation)> = store.lookup_signature(query, filter)?
         .into_iter()
-        .map(|location| {
-            let result = Driver::get_doc(&location).unwrap();
+        .take(20)
+        .collect();
 
-            result.format().to_string()
-        })
+    if results.is_empty() {
+        println!("No functions match \"{}\".", query);
+        return Ok(());
+    }
+
+    if enable_pager {
+        setup_pager();
+    }
+
+    for (_, location) in results {
+        print_summary(location);
+    }
+
+    Ok(())
+}
+
+/// Prints a result using only the information in the store index.
+fn print_summary(location: &StoreLocation) {
+    println!("  {} ({})", location, location.doc_type.kind_name());
+    if !location.signature.is_empty() {
+        println!("      {}", location.signature);
+    }
+    if !location.summary.is_empty() {
+        println!("      {}", location.short_summary(SUMMARY_WIDTH));
+    }
+}
+
+fn print_search_query(query: &str,
+                      enable_pager: bool,
+                      brief: bool,
+                      filter: &SearchFilter,
+                      format_options: &FormatOptions) -> Result<()> {
+    let store = Store::load_layered()?;
+
+    let mut results: Vec<&StoreLocation> = store.lookup_filtered(query, filter)
+        .into_iter()
+        .take(10)
         .collect();
+    let mut note = None;
+
+    if results.is_empty() {
+        // Items like `String::trim` may only exist on the `Deref` target of a type.
+        if let Some((target, deref_results)) = store.lookup_through_deref(query) {
+            note = Some(format!("Note: \"{}\" was found through Deref<Target = {}>.", query, target));
+            results = deref_results.into_iter()
+                .filter(|location| filter.matches(location))
+                .take(10)
+                .collect();
+        }
+    }
+
+    if results.is_empty() {
+        println!("No results for \"{}\".", query);
+        return Ok(());
+    }
+
+    // When brief, only the best match is loaded from its archive. The others are listed with the
+    // summaries kept in the index.
+    let mut formatted: Vec<String> = Vec::new();
+    let mut others = Vec::new();
+    for location in results {
+        if brief && !formatted.is_empty() {
+            others.push(location);
+            continue;
+        }
+
+        let mut result = match Driver::get_doc(&location) {
+            Ok(doc) => doc,
+            Err(e @ Error(ErrorKind::ArchiveChanged(..), _)) => return Err(e),
+            Err(e) => {
+                eprintln!("Could not load documentation for {}: {}. Run oxidoc doctor to check \
+                          the documentation store.", location, e);
+                continue;
+            }
+        };
+        Driver::add_deref_methods(&store, &mut result);
+
+        let mut text = result.format_with(format_options).to_string();
+
+        let newest = store.newest_version(&location.crate_info.name);
+        if newest.map_or(false, |v| v != location.crate_info.version) {
+            text = format!("(from {} {}, the newest version documented is {})\n{}",
+                           location.crate_info.name,
+                           location.crate_info.version,
+                           newest.unwrap(),
+                           text);
+        }
+
+        formatted.push(text);
+    }
 
     if enable_pager {
         setup_pager();
     }
 
+    if let Some(note) = note {
+        println!("{}\n", note);
+    }
+
     for result in formatted {
         println!("{}", result);
     }
 
+    if !others.is_empty() {
+        println!("Other results:");
+        for location in others {
+            print_summary(location);
+        }
+    }
+
     Ok(())
 }
@@ -1,10 +1,11 @@
 use std::fmt;
 
-use ansi_term::Style;
+use ansi_term::{Colour, Style};
 use catmark::{self, OutputKind};
 use conversion::*;
 use document::ModPath;
 use generation::ast_ty_wrappers::{FnKind, Attributes};
+use libc;
 use term_size;
 
 pub enum Markup {
@@ -73,16 +74,41 @@ impl fmt::Display for MarkupDoc {
     }
 }
 
+/// Options that change how documentation is rendered.
+#[derive(Clone, Debug, Default)]
+pub struct FormatOptions {
+    /// Print the source of default method bodies.
+    pub show_source: bool,
+
+    /// Highlight keywords like `unsafe` in signatures with terminal colors.
+    pub highlight: bool,
+}
+
+/// Whether standard output is a terminal, so signatures can be highlighted.
+#[cfg(unix)]
+pub fn stdout_is_terminal() -> bool {
+    unsafe { libc::isatty(libc::STDOUT_FILENO) != 0 }
+}
+
+#[cfg(not(unix))]
+pub fn stdout_is_terminal() -> bool {
+    false
+}
+
 /// Describes an item that can be inserted into documentation markup.
 pub trait Format {
-    fn format(&self) -> MarkupDoc;
+    fn format(&self) -> MarkupDoc {
+        self.format_with(&FormatOptions::default())
+    }
+
+    fn format_with(&self, options: &FormatOptions) -> MarkupDoc;
 }
 
 impl Format for Documentation {
-    fn format(&self) -> MarkupDoc {
+    fn format_with(&self, options: &FormatOptions) -> MarkupDoc {
         let header = doc_header(self);
         let info = doc_inner_info(self);
-        let signature = doc_signature(self);
+        let signature = doc_signature(self, options);
         let body = doc_body(self);
         let related_items = doc_related_items(self);
 
@@ -98,13 +124,13 @@ impl Format for Documentation {
 }
 
 impl Format for ModPath {
-    fn format(&self) -> MarkupDoc {
+    fn format_with(&self, _options: &FormatOptions) -> MarkupDoc {
         MarkupDoc::new(vec![Header(self.to_string())])
     }
 }
 
 impl Format for Attributes {
-    fn format(&self) -> MarkupDoc {
+    fn format_with(&self, _options: &FormatOptions) -> MarkupDoc {
         let body = self.doc_strings.join("\n");
 
         MarkupDoc::new(vec![Markdown(body)])
@@ -119,13 +145,22 @@ fn doc_header(data: &Documentation) -> MarkupDoc {
         DocInnerData::EnumDoc(..) => "Enum",
         DocInnerData::TraitDoc(..) => "Trait",
         DocInnerData::TraitItemDoc(..) => "Trait Item",
+        DocInnerData::PrimitiveDoc(..) => "Primitive Type",
         DocInnerData::ModuleDoc(ref module) => if module.is_crate { "Crate" } else { "Module" },
     };
 
-    MarkupDoc::new(vec![
+    let mut parts = vec![
         Block(format!("({})", data.crate_info)),
         Header(format!("{} {}", name, data.mod_path)),
-    ])
+    ];
+
+    if data.attrs.hidden {
+        parts.push(Section("Hidden item (#[doc(hidden)])".to_string()));
+    } else if data.is_private() {
+        parts.push(Section("Private item".to_string()));
+    }
+
+    MarkupDoc::new(parts)
 }
 
 fn doc_body(data: &Documentation) -> MarkupDoc {
@@ -133,7 +168,42 @@ fn doc_body(data: &Documentation) -> MarkupDoc {
 }
 
 fn doc_related_items(data: &Documentation) -> MarkupDoc {
-    MarkupDoc::new(vec![])
+    let mut parts = Vec::new();
+
+    for category in data.subitem_categories() {
+        if let Some(items) = data.links.get(&category) {
+            if items.is_empty() {
+                continue;
+            }
+
+            let names = items.iter()
+                .map(|item| format!("  {}", item.name))
+                .collect::<Vec<String>>()
+                .join("\n");
+
+            let title = match category {
+                DocType::DerefMethod => {
+                    let target = data.deref_target_type().map_or("".to_string(), |t| t.name.clone());
+                    format!("Methods from Deref<Target = {}>", target)
+                }
+                _ => category.to_string(),
+            };
+
+            parts.push(LineBreak);
+            parts.push(Section(title));
+            parts.push(Block(names));
+        }
+    }
+
+    // Targets like the `T` of `Box<T>` have no methods to list, but are still worth knowing.
+    if data.deref_target().is_none() {
+        if let Some(target) = data.deref_target_type() {
+            parts.push(LineBreak);
+            parts.push(Section(format!("Deref<Target = {}>", target.name)));
+        }
+    }
+
+    MarkupDoc::new(parts)
 }
 
 fn doc_inner_info(data: &Documentation) -> MarkupDoc {
@@ -147,19 +217,29 @@ fn doc_inner_info(data: &Documentation) -> MarkupDoc {
                 _ => LineBreak,
             }
         }
-        DocInnerData::TraitItemDoc(..) => {
-            Header(format!("From trait {}", data.mod_path.parent().unwrap()))
+        DocInnerData::TraitItemDoc(ref item) => {
+            let trait_path = data.mod_path.parent().unwrap();
+            match item.node {
+                TraitItemKind::Method(_, None) => {
+                    Header(format!("Required method of trait {}", trait_path))
+                }
+                TraitItemKind::Method(_, Some(..)) => {
+                    Header(format!("Provided method of trait {}", trait_path))
+                }
+                _ => Header(format!("From trait {}", trait_path)),
+            }
         }
         DocInnerData::StructDoc(..) |
         DocInnerData::ConstDoc(..) |
         DocInnerData::EnumDoc(..) |
         DocInnerData::TraitDoc(..) |
+        DocInnerData::PrimitiveDoc(..) |
         DocInnerData::ModuleDoc(..) => LineBreak,
     };
     MarkupDoc::new(vec![markup])
 }
 
-fn doc_signature(data: &Documentation) -> MarkupDoc {
+fn doc_signature(data: &Documentation, options: &FormatOptions) -> MarkupDoc {
     let vis_string = match data.visibility {
         Some(ref v) => v.to_string(),
         None => "".to_string(),
@@ -177,8 +257,15 @@ fn doc_signature(data: &Documentation) -> MarkupDoc {
         DocInnerData::EnumDoc(..) => doc_enum(data),
         DocInnerData::StructDoc(..) => doc_struct(data),
         DocInnerData::ConstDoc(ref konst) => doc_const(data, konst),
-        DocInnerData::TraitDoc(..) => doc_trait(data),
-        DocInnerData::TraitItemDoc(ref item) => doc_trait_item(data, item),
+        DocInnerData::TraitDoc(ref trait_) => doc_trait(data, trait_),
+        DocInnerData::TraitItemDoc(ref item) => doc_trait_item(data, item, options),
+        DocInnerData::PrimitiveDoc(..) => doc_primitive(data),
+    };
+
+    let header = if options.highlight {
+        highlight_keywords(&header)
+    } else {
+        header
     };
 
     MarkupDoc::new(vec![
@@ -195,8 +282,32 @@ fn doc_module(data: &Documentation) -> String {
     format!("mod {}", data.mod_path)
 }
 
+/// Renders the qualifiers that come before `fn`, like `const unsafe extern "C" `.
+fn fn_qualifier_prefix(constness: &Constness,
+                       asyncness: &Asyncness,
+                       unsafety: &Unsafety,
+                       abi: &Abi) -> String {
+    fn_qualifiers(constness, asyncness, unsafety, abi)
+        .into_iter()
+        .map(|q| q + " ")
+        .collect()
+}
+
+/// Highlights the `unsafe` keywords in a plain signature.
+fn highlight_keywords(signature: &str) -> String {
+    signature.split(' ')
+        .map(|word| if word == "unsafe" {
+            Colour::Red.bold().paint(word).to_string()
+        } else {
+            word.to_string()
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
 fn doc_fn(data: &Documentation, func: &Function) -> String {
-    format!("fn {} {}", data.name, func.header)
+    let qualifiers = fn_qualifier_prefix(&func.constness, &func.asyncness, &func.unsafety, &func.abi);
+    format!("{}fn {} {}", qualifiers, data.name, func.header)
 }
 
 fn doc_enum(data: &Documentation) -> String {
@@ -207,30 +318,50 @@ fn doc_struct(data: &Documentation) -> String {
     format!("struct {} {{ /* fields omitted */ }}", data.name)
 }
 
+fn doc_primitive(data: &Documentation) -> String {
+    format!("primitive {}", data.name)
+}
+
 fn doc_const(data: &Documentation, konst: &Constant) -> String {
     format!("const {}: {} = {}", data.name, konst.ty.name, konst.expr)
 }
 
-fn doc_trait(data: &Documentation) -> String {
-    format!("trait {} {{ /* fields omitted */ }}", data.name)
+fn doc_trait(data: &Documentation, trait_: &Trait) -> String {
+    let unsafety = match trait_.unsafety {
+        Unsafety::Unsafe => "unsafe ".to_string(),
+        Unsafety::Normal => "".to_string(),
+    };
+    format!("{}trait {} {{ /* fields omitted */ }}", unsafety, data.name)
 }
 
-fn doc_trait_item(data: &Documentation, item: &TraitItem) -> String {
+fn doc_trait_item(data: &Documentation, item: &TraitItem, options: &FormatOptions) -> String {
     let item_string = match item.node {
         TraitItemKind::Const(ref ty, ref expr) => {
-            let expr_string = match *expr {
-                Some(ref e) => e.clone(),
-                None => "".to_string(),
+            match *expr {
+                Some(ref e) => format!("const {}: {} = {};", data.name, ty.name, e),
+                None => format!("const {}: {};", data.name, ty.name),
+            }
+        }
+        TraitItemKind::Method(ref sig, ref body) => {
+            let body_string = match *body {
+                Some(ref b) if options.show_source => format!(" {}", b),
+                Some(..) => " { ... }".to_string(),
+                None => ";".to_string(),
             };
-            format!("const {}: {} = {}", data.name, ty.name, expr_string)
+            let qualifiers = fn_qualifier_prefix(&sig.constness, &sig.asyncness, &sig.unsafety, &sig.abi);
+            format!("{}fn {} {}{}", qualifiers, data.name, sig.header, body_string)
         }
-        TraitItemKind::Method(ref sig) => format!("fn {} {}", data.name, sig.header),
-        TraitItemKind::Type(ref ty) => {
-            let ty_string = match *ty {
-                Some(ref t) => t.name.clone(),
+        TraitItemKind::Type(ref bounds, ref ty) => {
+            let bounds_string = if bounds.is_empty() {
+                "".to_string()
+            } else {
+                format!(": {}", bounds.join(" + "))
+            };
+            let default_string = match *ty {
+                Some(ref t) => format!(" = {}", t.name),
                 None => "".to_string(),
             };
-            format!("type {}", ty_string)
+            format!("type {}{}{};", data.name, bounds_string, default_string)
         }
         TraitItemKind::Macro(ref mac) => format!("macro {} {}", data.name, mac),
     };
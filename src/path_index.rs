@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use search;
+
+/// A sorted index of the module paths in a store and the segments they are made of, built when
+/// the store is saved. Prefixes of paths and segments are found by binary search, and the paths
+/// containing a segment are kept sorted so queries with several segments intersect them in
+/// linear time.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PathIndex {
+    /// The lowercased module paths of all items, sorted and without duplicates. Paths are
+    /// referred to by their position.
+    /// ["std::collections::hashmap", "std::vec", "std::vec::vec", ...]
+    paths: Vec<String>,
+
+    /// The lowercased segments of the paths, sorted and without duplicates.
+    /// ["collections", "hashmap", "std", "vec", ...]
+    segments: Vec<String>,
+
+    /// The characters of each segment as a bit set, so segments which can't match a query
+    /// fuzzily are skipped without scoring them.
+    masks: Vec<u64>,
+
+    /// The sorted positions of the paths containing each segment.
+    postings: Vec<Vec<u32>>,
+}
+
+impl PathIndex {
+    pub fn new() -> Self {
+        PathIndex::default()
+    }
+
+    /// Builds the index of the given module paths.
+    pub fn build<I>(paths: I) -> Self
+        where I: IntoIterator<Item = String>
+    {
+        let mut paths: Vec<String> = paths.into_iter().map(|p| p.to_lowercase()).collect();
+        paths.sort();
+        paths.dedup();
+
+        let (segments, postings) = {
+            let mut postings: HashMap<&str, Vec<u32>> = HashMap::new();
+            for (i, path) in paths.iter().enumerate() {
+                for segment in path.split("::") {
+                    let ids = postings.entry(segment).or_insert(Vec::new());
+                    // Paths are visited in order, so the positions stay sorted.
+                    if ids.last() != Some(&(i as u32)) {
+                        ids.push(i as u32);
+                    }
+                }
+            }
+
+            let mut postings: Vec<(&str, Vec<u32>)> = postings.into_iter().collect();
+            postings.sort_by(|a, b| a.0.cmp(b.0));
+
+            let segments: Vec<String> = postings.iter().map(|&(s, _)| s.to_string()).collect();
+            let postings: Vec<Vec<u32>> = postings.into_iter().map(|(_, ids)| ids).collect();
+            (segments, postings)
+        };
+        let masks = segments.iter().map(|s| char_mask(s)).collect();
+
+        PathIndex {
+            paths: paths,
+            segments: segments,
+            masks: masks,
+            postings: postings,
+        }
+    }
+
+    /// Returns the number of paths in the index.
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// Returns the paths starting with `prefix`, ignoring case, like `std::collections::hashmap`
+    /// for `std::coll`.
+    pub fn paths_with_prefix(&self, prefix: &str) -> &[String] {
+        let range = prefix_range(&self.paths, &prefix.to_lowercase());
+        &self.paths[range]
+    }
+
+    /// Returns the paths of the items whose names start with `prefix`, ignoring case, shortest
+    /// first.
+    pub fn paths_with_name_prefix(&self, prefix: &str) -> Vec<&str> {
+        let prefix = prefix.to_lowercase();
+        let range = prefix_range(&self.segments, &prefix);
+
+        let mut ids: Vec<u32> = self.postings[range].iter().flat_map(|ids| ids.iter().cloned()).collect();
+        ids.sort();
+        ids.dedup();
+
+        let mut paths: Vec<&str> = ids.into_iter()
+            .map(|id| self.paths[id as usize].as_str())
+            .filter(|path| path.rsplit("::").next().map_or(false, |name| name.starts_with(&prefix)))
+            .collect();
+        paths.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+        paths
+    }
+
+    /// Returns the paths matching all segments of the query in order, exactly or fuzzily, like
+    /// `collections::hashmp` for `std::collections::hashmap`. The query segments have to be
+    /// lowercase, see `search::query_segments`.
+    pub fn matching_paths(&self, query: &[String]) -> Vec<&str> {
+        let mut result: Option<Vec<u32>> = None;
+
+        for segment in query.iter() {
+            let ids = self.paths_matching_segment(segment);
+            let ids = match result {
+                Some(result) => intersect_sorted(&result, &ids),
+                None         => ids,
+            };
+
+            if ids.is_empty() {
+                return Vec::new();
+            }
+            result = Some(ids);
+        }
+
+        result.unwrap_or(Vec::new())
+            .into_iter()
+            .map(|id| self.paths[id as usize].as_str())
+            .filter(|path| {
+                let segments: Vec<&str> = path.split("::").collect();
+                search::score_path(query, &segments).is_some()
+            })
+            .collect()
+    }
+
+    /// Returns the sorted positions of the paths with a segment matching the query segment.
+    fn paths_matching_segment(&self, query: &str) -> Vec<u32> {
+        let mut ids: Vec<u32> = Vec::new();
+        if query.is_empty() {
+            return ids;
+        }
+
+        if search::is_fuzzy_query(query) {
+            let query_mask = char_mask(query);
+            let max_missing = search::max_typos(query) as u32;
+
+            for (i, segment) in self.segments.iter().enumerate() {
+                // Characters of the query missing from the segment can only be typos.
+                if (query_mask & !self.masks[i]).count_ones() > max_missing {
+                    continue;
+                }
+                if search::score_segment(query, segment).is_some() {
+                    ids.extend(self.postings[i].iter().cloned());
+                }
+            }
+        } else {
+            // Short queries only match exactly or as a prefix.
+            for postings in self.postings[prefix_range(&self.segments, query)].iter() {
+                ids.extend(postings.iter().cloned());
+            }
+        }
+
+        ids.sort();
+        ids.dedup();
+        ids
+    }
+}
+
+/// Returns the range of the sorted strings starting with `prefix`.
+fn prefix_range(sorted: &[String], prefix: &str) -> Range<usize> {
+    let start = match sorted.binary_search_by(|s| s.as_str().cmp(prefix)) {
+        Ok(i) | Err(i) => i,
+    };
+    let len = sorted[start..].iter().take_while(|s| s.starts_with(prefix)).count();
+    start..start + len
+}
+
+/// Returns the values in both sorted lists.
+fn intersect_sorted(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < a.len() && j < b.len() {
+        if a[i] < b[j] {
+            i += 1;
+        } else if a[i] > b[j] {
+            j += 1;
+        } else {
+            result.push(a[i]);
+            i += 1;
+            j += 1;
+        }
+    }
+
+    result
+}
+
+/// Returns the set of characters in a lowercase string as bits: one for each letter and digit,
+/// one for `_`, and the rest shared by other characters. If a string contains a character of
+/// another, its mask contains the character's bit.
+fn char_mask(s: &str) -> u64 {
+    s.chars().fold(0u64, |mask, c| {
+        let bit = match c.to_digit(36) {
+            Some(digit)      => digit,
+            None if c == '_' => 36,
+            None             => 37 + c as u32 % 27,
+        };
+        mask | (1u64 << bit)
+    })
+}
@@ -1,9 +1,37 @@
-use std::{env, fs, io, result};
-use std::path::{PathBuf};
+use std::{env, fs, io, result, str};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use semver::Version;
+use toml;
+
 use document::CrateInfo;
+use toml_util;
 use ::errors::*;
 
-const STORE_FILENAME: &str = "store";
+pub const STORE_LOCK_FILENAME: &str = "store.lock";
+
+/// Name of the directory holding a project-local store, searched for from the current directory
+/// upwards.
+const PROJECT_STORE_DIRNAME: &str = ".oxidoc";
+
+lazy_static! {
+    /// The store root given on the command line, which overrides the environment and config.
+    static ref STORE_ROOT_OVERRIDE: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+    static ref CONFIG: Mutex<Option<Config>> = Mutex::new(None);
+}
+
+/// Settings read from `$XDG_CONFIG_HOME/oxidoc/config.toml`.
+#[derive(Clone, Default, Deserialize)]
+struct Config {
+    /// The writable store documentation is generated into.
+    store: Option<PathBuf>,
+
+    /// Read-only stores searched after the project-local and writable stores.
+    #[serde(default)]
+    shared_stores: Vec<PathBuf>,
+}
 
 pub fn home_dir() -> Result<PathBuf> {
     if let Some(home_dir) = env::home_dir() {
@@ -13,13 +41,69 @@ pub fn home_dir() -> Result<PathBuf> {
     }
 }
 
+/// Returns `$CARGO_HOME`, or `~/.cargo` if it isn't set.
+pub fn cargo_home() -> Result<PathBuf> {
+    if let Some(cargo_home) = env::var_os("CARGO_HOME") {
+        return Ok(PathBuf::from(cargo_home));
+    }
+
+    Ok(home_dir()?.join(".cargo"))
+}
+
 fn make_registry_path(dir: &str) -> Result<PathBuf> {
-    let home_dir = home_dir()?;
+    Ok(cargo_home()?.join("registry").join(dir))
+}
+
+fn config_file_path() -> Result<PathBuf> {
+    let config_dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None      => home_dir()?.join(".config"),
+    };
+
+    Ok(config_dir.join("oxidoc").join("config.toml"))
+}
+
+fn load_config() -> Result<Config> {
+    let path = config_file_path()?;
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+
+    let data = toml_util::toml_string_from_file(&path)?;
+    toml::from_str(&data).chain_err(|| format!("Invalid config file {}", path.display()))
+}
 
-    Ok(home_dir.as_path().join(".cargo").join("registry").join(dir))
+fn config() -> Result<Config> {
+    let mut config = CONFIG.lock().unwrap();
+    if config.is_none() {
+        *config = Some(load_config()?);
+    }
+
+    Ok(config.clone().unwrap())
+}
+
+/// Sets the root of the writable store, taking precedence over `OXIDOC_STORE` and the config
+/// file.
+pub fn set_store_root(path: PathBuf) {
+    *STORE_ROOT_OVERRIDE.lock().unwrap() = Some(path);
 }
 
+/// Returns the root of the writable store documentation is generated into. In order of
+/// precedence, this is the `--store` flag, the `OXIDOC_STORE` environment variable, the `store`
+/// key of the config file and finally `$CARGO_HOME/registry/doc`.
 pub fn doc_registry_path() -> Result<PathBuf> {
+    if let Some(ref root) = *STORE_ROOT_OVERRIDE.lock().unwrap() {
+        return Ok(root.clone());
+    }
+
+    if let Some(root) = env::var_os("OXIDOC_STORE") {
+        return Ok(PathBuf::from(root));
+    }
+
+    if let Some(root) = config()?.store {
+        return Ok(root);
+    }
+
     make_registry_path("doc")
 }
 
@@ -27,20 +111,178 @@ pub fn src_registry_path() -> Result<PathBuf> {
     make_registry_path("src")
 }
 
-pub fn store_file_path() -> Result<PathBuf> {
-    let mut registry_path = doc_registry_path()?;
-    registry_path.push(STORE_FILENAME);
-    Ok(registry_path)
+/// Returns the roots of all stores searched by queries, in order of precedence: the
+/// project-local store, the writable store, and the shared stores from `OXIDOC_SHARED_STORES`
+/// and the config file.
+pub fn store_roots() -> Result<Vec<PathBuf>> {
+    let mut roots = Vec::new();
+
+    if let Some(project_root) = project_store_path() {
+        roots.push(project_root);
+    }
+
+    roots.push(doc_registry_path()?);
+
+    if let Some(shared) = env::var_os("OXIDOC_SHARED_STORES") {
+        roots.extend(env::split_paths(&shared));
+    }
+
+    roots.extend(config()?.shared_stores);
+
+    let mut unique: Vec<PathBuf> = Vec::new();
+    for root in roots {
+        if !unique.contains(&root) {
+            unique.push(root);
+        }
+    }
+
+    Ok(unique)
+}
+
+/// Finds a `.oxidoc` store directory in the current directory or one of its parents.
+fn project_store_path() -> Option<PathBuf> {
+    find_in_current_dir_or_parents(|dir| {
+        let store_dir = dir.join(PROJECT_STORE_DIRNAME);
+        if store_dir.is_dir() { Some(store_dir) } else { None }
+    })
+}
+
+/// Finds the `Cargo.toml` of the Cargo project in the current directory or one of its parents.
+pub fn project_manifest_path() -> Option<PathBuf> {
+    find_in_current_dir_or_parents(|dir| {
+        let manifest = dir.join("Cargo.toml");
+        if manifest.is_file() { Some(manifest) } else { None }
+    })
 }
 
+/// Returns the first result of `find` for the current directory and its parents, innermost
+/// first.
+fn find_in_current_dir_or_parents<F>(find: F) -> Option<PathBuf>
+    where F: Fn(&Path) -> Option<PathBuf>
+{
+    let current_dir = match env::current_dir() {
+        Ok(dir) => dir,
+        Err(_)  => return None,
+    };
+
+    let mut dir = Some(current_dir.as_path());
+    while let Some(d) = dir {
+        if let Some(found) = find(d) {
+            return Some(found);
+        }
+        dir = d.parent();
+    }
+
+    None
+}
+
+pub fn store_lock_path_in(root: &Path) -> PathBuf {
+    root.join(STORE_LOCK_FILENAME)
+}
+
+/// File extension of the archives holding the documentation of a crate version.
+pub const ARCHIVE_EXTENSION: &str = "odoc";
+
 /// Obtains the base output path for a crate's documentation.
 pub fn crate_doc_path(crate_info: &CrateInfo) -> Result<PathBuf> {
-    let registry_path = doc_registry_path()?;
+    Ok(crate_doc_path_in(&doc_registry_path()?, crate_info))
+}
+
+/// Obtains the base path for a crate's documentation in the store at `root`.
+pub fn crate_doc_path_in(root: &Path, crate_info: &CrateInfo) -> PathBuf {
+    root.join(format!("{}-{}", crate_info.name, crate_info.version))
+}
+
+/// Obtains the name of the archive holding a crate version's documentation in a store.
+pub fn crate_archive_name(crate_info: &CrateInfo) -> Result<String> {
+    Ok(format!("{}@{}.{}",
+               encode_file_name(&crate_info.name)?,
+               encode_file_name(&crate_info.version)?,
+               ARCHIVE_EXTENSION))
+}
+
+/// Whether `name` looks like the name of a crate version's archive in a store.
+pub fn is_archive_name(name: &str) -> bool {
+    name.contains('@') && name.ends_with(&format!(".{}", ARCHIVE_EXTENSION))
+}
+
+/// Returns the crate version an archive named `name` holds the documentation of, like
+/// `serde@1.0.9`, or `None` if it isn't the name of an archive.
+pub fn archive_crate_version(name: &str) -> Option<String> {
+    if !is_archive_name(name) {
+        return None;
+    }
+
+    let stem = &name[..name.len() - ARCHIVE_EXTENSION.len() - 1];
+    let mut parts = stem.splitn(2, '@');
+    match (parts.next().and_then(decode_file_name), parts.next().and_then(decode_file_name)) {
+        (Some(krate), Some(version)) => Some(format!("{}@{}", krate, version)),
+        _                            => None,
+    }
+}
+
+/// Whether `name` looks like a crate directory of old versions of oxidoc, which wrote a file per
+/// document into `crate-version` directories.
+pub fn is_legacy_crate_dir_name(name: &str) -> bool {
+    name.char_indices()
+        .filter(|&(_, c)| c == '-')
+        .any(|(i, _)| i > 0 && Version::parse(&name[i + 1..]).is_ok())
+}
+
+/// Longest name `encode_file_name` accepts, leaving room for the rest of the file name within
+/// the usual limit of 255 bytes.
+const MAX_ENCODED_NAME_LEN: usize = 100;
+
+/// Encodes a name for use in a file name. Uppercase letters and anything that may not be valid
+/// in a file name are escaped as `%XX`, so different names never map to the same file, even on
+/// case-insensitive filesystems.
+pub fn encode_file_name(name: &str) -> Result<String> {
+    let mut encoded = String::new();
+
+    for byte in name.bytes() {
+        let is_lowercase = byte >= b'a' && byte <= b'z';
+        let is_digit = byte >= b'0' && byte <= b'9';
+
+        if is_lowercase || is_digit || byte == b'_' || byte == b'-' || byte == b'+' {
+            encoded.push(byte as char);
+        } else if byte == b'.' && !encoded.is_empty() {
+            encoded.push('.');
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+
+    if encoded.is_empty() || encoded.len() > MAX_ENCODED_NAME_LEN {
+        bail!(ErrorKind::NameEncodingError(name.to_string()));
+    }
+
+    Ok(encoded)
+}
+
+/// Decodes a name encoded by `encode_file_name`, or returns `None` if it isn't one.
+pub fn decode_file_name(encoded: &str) -> Option<String> {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::new();
+
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = match bytes.get(i + 1..i + 3).map(str::from_utf8) {
+                Some(Ok(hex)) => hex,
+                _             => return None,
+            };
+            match u8::from_str_radix(hex, 16) {
+                Ok(byte) => decoded.push(byte),
+                Err(_)   => return None,
+            }
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
 
-    let path = registry_path.join(format!("{}-{}",
-                                          crate_info.name,
-                                          crate_info.version));
-    Ok(path)
+    String::from_utf8(decoded).ok()
 }
 
 pub fn iter_crate_source_paths() -> Result<Vec<PathBuf>> {
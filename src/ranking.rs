@@ -0,0 +1,215 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use toml::Value;
+
+use conversion::DocType;
+use paths;
+use search;
+use store::{self, StoreLocation};
+use toml_util;
+use ::errors::*;
+
+/// The crates of the standard library, which are preferred over other crates.
+const STD_CRATES: &'static [&'static str] = &["std", "core", "alloc", "proc_macro"];
+
+/// The tables of a Cargo manifest listing dependencies.
+const DEPENDENCY_TABLES: &'static [&'static str] = &[
+    "dependencies", "dev-dependencies", "build-dependencies",
+];
+
+/// How much each signal counts towards the rank of a search result. The score of a result is the
+/// sum of the weights of the signals that apply to it, plus how well the query matched its path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RankingWeights {
+    /// For an item named exactly like the last segment of the query, ignoring case.
+    pub exact_name: i64,
+    /// For an item whose name starts with the last segment of the query.
+    pub name_prefix: i64,
+    /// For items of the kind the query looks like it is for: types and traits for capitalized
+    /// queries like `Vec`, constants for uppercase ones like `MAX`, and modules, functions,
+    /// macros and primitive types for lowercase ones.
+    pub kind: i64,
+    /// For every segment of the item's path, so shallower paths rank higher. Usually negative.
+    pub depth: i64,
+    /// For items of `std`, `core` and the other standard library crates.
+    pub std_crate: i64,
+    /// For items of crates the current project depends on.
+    pub dependency: i64,
+}
+
+impl Default for RankingWeights {
+    fn default() -> Self {
+        // A match of a better kind, like an exact match over a prefix match, scores about 200
+        // more in `search::score_path`. The other signals are smaller than that, so they mostly
+        // order matches of the same kind. The standard library is preferred over dependencies.
+        RankingWeights {
+            exact_name: 300,
+            name_prefix: 100,
+            kind: 100,
+            depth: -10,
+            std_crate: 50,
+            dependency: 40,
+        }
+    }
+}
+
+/// Ranks the results of searches by name.
+#[derive(Clone, Debug, Default)]
+pub struct Ranking {
+    pub weights: RankingWeights,
+    /// The names of the crates the current project depends on, with `-` replaced by `_`.
+    pub dependencies: HashSet<String>,
+}
+
+impl Ranking {
+    /// Creates a ranking with the default weights which doesn't know about any project.
+    pub fn new() -> Self {
+        Ranking::default()
+    }
+
+    /// Creates a ranking which prefers the dependencies of the Cargo project in the current
+    /// directory or one of its parents, if there is one.
+    pub fn for_current_project() -> Self {
+        let mut ranking = Ranking::new();
+
+        if let Some(manifest) = paths::project_manifest_path() {
+            match manifest_dependencies(&manifest) {
+                Ok(dependencies) => ranking.dependencies = dependencies,
+                Err(e) => warn!("Could not read dependencies from {}: {}", manifest.display(), e),
+            }
+        }
+
+        ranking
+    }
+
+    /// Scores a result of a query like `vec::Vec`. Higher scores rank first.
+    pub fn score(&self, query: &str, location: &StoreLocation) -> i64 {
+        let segments = search::query_segments(query);
+        let mod_path = location.mod_path.to_string();
+        let path_segments: Vec<&str> = mod_path.split("::").collect();
+        let mut score = search::score_path(&segments, &path_segments).unwrap_or(0);
+
+        let weights = &self.weights;
+        let query_name = query.split("::").last().unwrap_or("");
+        let name = location.name.to_lowercase();
+
+        if !query_name.is_empty() {
+            if name == query_name.to_lowercase() {
+                score += weights.exact_name;
+            } else if name.starts_with(&query_name.to_lowercase()) {
+                score += weights.name_prefix;
+            }
+        }
+
+        if preferred_kind(query_name, &location.doc_type) {
+            score += weights.kind;
+        }
+
+        score += weights.depth * path_segments.len() as i64;
+
+        let krate = normalize_crate_name(&location.crate_info.name);
+        if STD_CRATES.contains(&krate.as_str()) {
+            score += weights.std_crate;
+        }
+        if self.dependencies.contains(&krate) {
+            score += weights.dependency;
+        }
+
+        score
+    }
+
+    /// Sorts the results of a query with the best first. Results with the same score are ordered
+    /// by path, crate and version, newest first, so the order doesn't depend on the store.
+    pub fn sort(&self, query: &str, results: &mut Vec<&StoreLocation>) {
+        let mut scored: Vec<(i64, &StoreLocation)> = results.drain(..)
+            .map(|loc| (self.score(query, loc), loc))
+            .collect();
+
+        scored.sort_by(|&(score_a, a), &(score_b, b)| {
+            score_b.cmp(&score_a)
+                .then_with(|| a.mod_path.0.len().cmp(&b.mod_path.0.len()))
+                .then_with(|| a.mod_path.to_string().cmp(&b.mod_path.to_string()))
+                .then_with(|| a.crate_info.name.cmp(&b.crate_info.name))
+                .then_with(|| store::compare_versions(&b.crate_info.version, &a.crate_info.version))
+                .then_with(|| a.doc_type.kind_name().cmp(b.doc_type.kind_name()))
+        });
+
+        results.extend(scored.into_iter().map(|(_, loc)| loc));
+    }
+}
+
+/// Whether the kind of an item is what a query for `name` most likely looks for, judging by
+/// its case.
+fn preferred_kind(name: &str, doc_type: &DocType) -> bool {
+    let first = match name.chars().next() {
+        Some(c) => c,
+        None    => return false,
+    };
+
+    let has_lowercase = name.chars().any(|c| c.is_lowercase());
+    if !has_lowercase && name.chars().count() > 1 {
+        return match *doc_type {
+            DocType::Const | DocType::AssocConst | DocType::TraitItemConst => true,
+            _ => false,
+        };
+    }
+
+    if first.is_uppercase() {
+        match *doc_type {
+            DocType::Struct | DocType::Enum | DocType::Trait => true,
+            _ => false,
+        }
+    } else {
+        match *doc_type {
+            DocType::Module | DocType::Function | DocType::Macro | DocType::Primitive => true,
+            _ => false,
+        }
+    }
+}
+
+fn normalize_crate_name(name: &str) -> String {
+    name.replace('-', "_")
+}
+
+/// Reads the names of the crates a Cargo manifest depends on, including development, build and
+/// platform-specific dependencies.
+pub fn manifest_dependencies(manifest: &Path) -> Result<HashSet<String>> {
+    let data = toml_util::toml_string_from_file(manifest)?;
+    parse_dependencies(&data)
+}
+
+/// Like `manifest_dependencies`, for the contents of a manifest.
+pub fn parse_dependencies(manifest: &str) -> Result<HashSet<String>> {
+    let value = toml_util::toml_value_from_string(manifest)?;
+    let mut dependencies = HashSet::new();
+
+    add_dependencies(&value, &mut dependencies);
+
+    // [target.'cfg(unix)'.dependencies]
+    if let Ok(&Value::Table(ref targets)) = toml_util::get_value_in_table(&value, "target") {
+        for target in targets.values() {
+            add_dependencies(target, &mut dependencies);
+        }
+    }
+
+    Ok(dependencies)
+}
+
+fn add_dependencies(table: &Value, dependencies: &mut HashSet<String>) {
+    for table_name in DEPENDENCY_TABLES.iter() {
+        let deps = match toml_util::get_value_in_table(table, table_name) {
+            Ok(&Value::Table(ref deps)) => deps,
+            _ => continue,
+        };
+
+        for (name, dep) in deps.iter() {
+            // Renamed dependencies give the crate's real name as `package`.
+            let package = match *dep {
+                Value::Table(ref dep) => dep.get("package").and_then(|p| p.as_str()),
+                _ => None,
+            };
+            dependencies.insert(normalize_crate_name(package.unwrap_or(name.as_str())));
+        }
+    }
+}
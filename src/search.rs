@@ -0,0 +1,355 @@
+use std::cmp;
+
+use semver::{Version, VersionReq};
+use strsim::damerau_levenshtein;
+
+use conversion::DocType;
+use store::StoreLocation;
+
+/// Scores of the ways a query segment can match a path segment. Every kind of match scores higher
+/// than all matches of the kinds below it, so exact and prefix matches rank first.
+const EXACT_SCORE: i64 = 1000;
+const PREFIX_SCORE: i64 = 800;
+const SUBSTRING_SCORE: i64 = 600;
+const SUBSEQUENCE_SCORE: i64 = 400;
+const TYPO_SCORE: i64 = 200;
+
+/// The range of scores within one kind of match.
+const SCORE_RANGE: i64 = 199;
+
+/// Added to the score of a path if the last query segment matched the item's name.
+const ITEM_NAME_BONUS: i64 = 100;
+
+/// Query segments shorter than this only match exactly or as a prefix, since most identifiers
+/// contain any one or two letters.
+const MIN_FUZZY_LEN: usize = 3;
+
+/// Query segments shorter than this aren't matched with typos.
+const MIN_TYPO_LEN: usize = 4;
+
+/// Scores for characters of a subsequence match, like fzf: matching at the start of a word and
+/// right after the previous match is rewarded, skipping characters is penalized.
+const CHAR_SCORE: i64 = 16;
+const BOUNDARY_BONUS: i64 = 8;
+const CONSECUTIVE_BONUS: i64 = 12;
+const GAP_PENALTY: i64 = 1;
+
+/// Splits a search query into lowercase path segments.
+pub fn query_segments(query: &str) -> Vec<String> {
+    query.to_lowercase().split("::").map(|s| s.to_string()).collect()
+}
+
+/// Scores how well a lowercase query segment matches a path segment, ignoring case. Returns
+/// `None` if it doesn't match.
+///
+/// Besides exact, prefix and substring matches, the query may be a subsequence of the segment,
+/// like `hshmp` for `HashMap`, or contain a typo or two, like `hasmhap`.
+pub fn score_segment(query: &str, segment: &str) -> Option<i64> {
+    if query.is_empty() {
+        return None;
+    }
+
+    let segment_lower = segment.to_lowercase();
+    let query_len = query.chars().count() as i64;
+    let segment_len = cmp::max(segment_lower.chars().count() as i64, 1);
+
+    if segment_lower == query {
+        return Some(EXACT_SCORE);
+    }
+
+    if segment_lower.starts_with(query) {
+        return Some(PREFIX_SCORE + SCORE_RANGE * query_len / segment_len);
+    }
+
+    if !is_fuzzy_query(query) {
+        return None;
+    }
+
+    if segment_lower.contains(query) {
+        return Some(SUBSTRING_SCORE + SCORE_RANGE * query_len / segment_len);
+    }
+
+    if let Some(score) = subsequence_score(query, segment) {
+        return Some(SUBSEQUENCE_SCORE + score);
+    }
+
+    typo_score(query, &segment_lower).map(|score| TYPO_SCORE + score)
+}
+
+/// Scores a match of the query as a subsequence of the segment, from 0 to `SCORE_RANGE`.
+fn subsequence_score(query: &str, segment: &str) -> Option<i64> {
+    let query: Vec<char> = query.chars().collect();
+    let chars: Vec<char> = segment.chars().collect();
+    let lower: Vec<char> = segment.to_lowercase().chars().collect();
+
+    // Lowercasing may change the number of characters, in which case boundaries aren't known.
+    let boundaries: Vec<bool> = (0..lower.len()).map(|i| {
+        i == 0 || lower.len() != chars.len() || is_word_boundary(chars[i - 1], chars[i])
+    }).collect();
+
+    // best[j] is the best score of matching the query so far with its last character at `j`.
+    let mut best: Vec<Option<i64>> = vec![None; lower.len()];
+    for (i, &q) in query.iter().enumerate() {
+        let mut next = vec![None; lower.len()];
+
+        for j in 0..lower.len() {
+            if lower[j] != q {
+                continue;
+            }
+
+            let char_score = CHAR_SCORE + if boundaries[j] { BOUNDARY_BONUS } else { 0 };
+
+            if i == 0 {
+                next[j] = Some(char_score - GAP_PENALTY * j as i64);
+                continue;
+            }
+
+            next[j] = (0..j).filter_map(|k| best[k].map(|score| {
+                if k + 1 == j {
+                    score + CONSECUTIVE_BONUS
+                } else {
+                    score - GAP_PENALTY * (j - k - 1) as i64
+                }
+            })).max().map(|score| score + char_score);
+        }
+
+        best = next;
+    }
+
+    let max_score = (CHAR_SCORE + BOUNDARY_BONUS + CONSECUTIVE_BONUS) * query.len() as i64;
+    best.into_iter()
+        .filter_map(|score| score)
+        .max()
+        .map(|score| cmp::max(0, score) * SCORE_RANGE / max_score)
+}
+
+fn is_word_boundary(prev: char, current: char) -> bool {
+    prev == '_' || (prev.is_lowercase() && current.is_uppercase())
+}
+
+/// Whether a query segment may match segments fuzzily, rather than only exactly or as a prefix.
+pub fn is_fuzzy_query(query: &str) -> bool {
+    query.chars().count() >= MIN_FUZZY_LEN
+}
+
+/// The number of typos a query segment may contain and still match.
+pub fn max_typos(query: &str) -> usize {
+    let query_len = query.chars().count();
+    if query_len < MIN_TYPO_LEN {
+        0
+    } else if query_len <= 5 {
+        1
+    } else {
+        2
+    }
+}
+
+/// Scores a match of the query against the segment, or against its start, with a few typos.
+fn typo_score(query: &str, segment_lower: &str) -> Option<i64> {
+    let query_len = query.chars().count();
+    let max_typos = max_typos(query);
+    if max_typos == 0 {
+        return None;
+    }
+
+    let segment_start: String = segment_lower.chars().take(query_len).collect();
+    let typos = cmp::min(damerau_levenshtein(query, segment_lower),
+                         damerau_levenshtein(query, &segment_start));
+
+    if typos == 0 || typos > max_typos {
+        return None;
+    }
+
+    Some(SCORE_RANGE - 50 * typos as i64)
+}
+
+/// Scores how well the query segments match a module path. The query segments have to match
+/// segments of the path in the same order, but may skip some, so `hashmap::new` matches
+/// `std::collections::HashMap::new`. Returns `None` if the path doesn't match.
+pub fn score_path(query: &[String], path: &[&str]) -> Option<i64> {
+    if query.is_empty() || query.len() > path.len() {
+        return None;
+    }
+
+    // best[j] is the best score of matching the query so far with its last segment at `j`.
+    let mut best: Vec<Option<i64>> = vec![None; path.len()];
+    for (i, query_segment) in query.iter().enumerate() {
+        let mut next = vec![None; path.len()];
+
+        for (j, segment) in path.iter().enumerate() {
+            let score = match score_segment(query_segment, segment) {
+                Some(score) => score,
+                None        => continue,
+            };
+
+            next[j] = if i == 0 {
+                Some(score)
+            } else {
+                (0..j).filter_map(|k| best[k]).max().map(|previous| previous + score)
+            };
+        }
+
+        best = next;
+    }
+
+    best.iter().enumerate()
+        .filter_map(|(j, score)| score.map(|s| {
+            let bonus = if j + 1 == path.len() { ITEM_NAME_BONUS } else { 0 };
+            // Shorter paths are preferred among equally good matches.
+            s + bonus - path.len() as i64
+        }))
+        .max()
+}
+
+/// The names of the kinds of items searches can be restricted to, as given to `--kind` or as a
+/// query prefix like `fn:spawn`.
+pub const KIND_NAMES: &'static [&'static str] = &[
+    "fn", "method", "struct", "enum", "trait", "const", "mod", "macro",
+];
+
+/// A kind of item searches can be restricted to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ItemKind {
+    /// Free functions, but not methods.
+    Function,
+    /// Methods of types and traits.
+    Method,
+    Struct,
+    Enum,
+    Trait,
+    /// Constants, including associated constants.
+    Const,
+    Module,
+    Macro,
+}
+
+impl ItemKind {
+    /// Parses a kind name like `fn` or `struct`, ignoring case.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "fn" | "function"    => Some(ItemKind::Function),
+            "method"             => Some(ItemKind::Method),
+            "struct"             => Some(ItemKind::Struct),
+            "enum"               => Some(ItemKind::Enum),
+            "trait"              => Some(ItemKind::Trait),
+            "const" | "constant" => Some(ItemKind::Const),
+            "mod" | "module"     => Some(ItemKind::Module),
+            "macro"              => Some(ItemKind::Macro),
+            _                    => None,
+        }
+    }
+
+    pub fn matches(&self, location: &StoreLocation) -> bool {
+        match *self {
+            ItemKind::Function => location.doc_type == DocType::Function && !location.is_method,
+            ItemKind::Method   => location.is_method,
+            ItemKind::Struct   => location.doc_type == DocType::Struct,
+            ItemKind::Enum     => location.doc_type == DocType::Enum,
+            ItemKind::Trait    => location.doc_type == DocType::Trait,
+            ItemKind::Const    => match location.doc_type {
+                DocType::Const | DocType::AssocConst | DocType::TraitItemConst => true,
+                _ => false,
+            },
+            ItemKind::Module   => location.doc_type == DocType::Module,
+            ItemKind::Macro    => match location.doc_type {
+                DocType::Macro | DocType::TraitItemMacro => true,
+                _ => false,
+            },
+        }
+    }
+}
+
+/// Restrictions on the results of a search. The default filter lets every public item through.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchFilter {
+    /// The kinds of items to return, or any kind if empty.
+    pub kinds: Vec<ItemKind>,
+    /// Only return unsafe functions, methods and traits.
+    pub unsafe_only: bool,
+    /// Only return `const fn`s.
+    pub const_fn_only: bool,
+    /// Only return items from this crate.
+    pub krate: Option<String>,
+    /// Only return items from crate versions matching this exact version or version requirement,
+    /// like `0.1`.
+    pub version: Option<String>,
+    /// Also return private and `#[doc(hidden)]` items.
+    pub include_private: bool,
+}
+
+impl SearchFilter {
+    pub fn new() -> Self {
+        SearchFilter::default()
+    }
+
+    /// Adds the restrictions given as prefixes of a query, like `fn:spawn` or `unsafe:fn:read`,
+    /// to the filter and returns the rest of the query. `constfn:` selects const functions, since
+    /// `const:` is the kind of constants. Prefixes which aren't kind names, `unsafe` or `constfn`
+    /// are left in the query.
+    pub fn parse_prefixes<'a>(&mut self, query: &'a str) -> &'a str {
+        let mut rest = query;
+
+        loop {
+            let pos = match rest.find(':') {
+                Some(pos) => pos,
+                None      => return rest,
+            };
+
+            // `::` separates path segments.
+            if rest[pos + 1..].starts_with(':') {
+                return rest;
+            }
+
+            let prefix = rest[..pos].to_lowercase();
+            if prefix == "unsafe" {
+                self.unsafe_only = true;
+            } else if prefix == "constfn" {
+                self.const_fn_only = true;
+            } else if let Some(kind) = ItemKind::parse(&prefix) {
+                if !self.kinds.contains(&kind) {
+                    self.kinds.push(kind);
+                }
+            } else {
+                return rest;
+            }
+
+            rest = &rest[pos + 1..];
+        }
+    }
+
+    pub fn matches(&self, location: &StoreLocation) -> bool {
+        if location.is_private && !self.include_private {
+            return false;
+        }
+        if !self.kinds.is_empty() && !self.kinds.iter().any(|kind| kind.matches(location)) {
+            return false;
+        }
+        if (self.unsafe_only && !location.is_unsafe) ||
+            (self.const_fn_only && !location.is_const_fn) {
+            return false;
+        }
+        if let Some(ref krate) = self.krate {
+            if location.crate_info.name.to_lowercase() != krate.to_lowercase() {
+                return false;
+            }
+        }
+        if let Some(ref spec) = self.version {
+            if !version_matches(spec, &location.crate_info.version) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Whether a crate version is the given exact version or matches it as a version requirement.
+pub fn version_matches(spec: &str, version: &str) -> bool {
+    if spec == version {
+        return true;
+    }
+
+    match (VersionReq::parse(spec), Version::parse(version)) {
+        (Ok(req), Ok(version)) => req.matches(&version),
+        _ => false,
+    }
+}
@@ -1,54 +1,160 @@
 use conversion::Documentation;
+use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
 use std::fmt;
-use std::fs::{File, OpenOptions};
-use std::io::{Read, Write};
-use std::path::{Path, PathBuf};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read};
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use bincode::{self, Infinite};
+use libc;
+use semver::{Version, VersionReq};
 use serde::de::DeserializeOwned;
 use serde::ser::Serialize;
-use strsim::levenshtein;
 
+use backend::{self, Backend, FsBackend};
+use bundle::Bundle;
 use conversion::DocType;
 use document::CrateInfo;
 use document::ModPath;
+use path_index::PathIndex;
 use paths;
+use ranking::Ranking;
+use search::{self, SearchFilter};
+use text_search::{self, DocsetTextIndex, TextIndex, TEXT_INDEX_NAME};
+use type_search::{DocsetSignatures, SignatureIndex, SignatureQuery, SIGNATURE_INDEX_NAME};
 use ::errors::*;
 
-fn create_or_open_file<T: AsRef<Path>>(path: T) -> Result<File> {
-    let path_as = path.as_ref();
-    if !path_as.exists() {
-        OpenOptions::new()
-            .read(true)
+/// Name of the store index in a backend.
+const STORE_NAME: &str = "store";
+
+/// An advisory lock on the writable store, held while updating it so that several processes
+/// generating documentation at the same time don't overwrite each other's changes. The lock is
+/// released when this is dropped. Readers don't need it, since files are replaced atomically and
+/// reads of an archive which was regenerated fail with `ErrorKind::ArchiveChanged`. The lock file
+/// itself is never removed, or processes would lock different files.
+pub struct StoreLock {
+    _file: File,
+}
+
+impl StoreLock {
+    /// Waits until no other process holds the lock and acquires it.
+    pub fn acquire() -> Result<StoreLock> {
+        StoreLock::acquire_in(&paths::doc_registry_path()?)
+    }
+
+    /// Acquires the lock of the store in `root`.
+    pub fn acquire_in(root: &Path) -> Result<StoreLock> {
+        fs::create_dir_all(root)
+            .chain_err(|| format!("Failed to create directory {}", root.display()))?;
+
+        let lock_path = paths::store_lock_path_in(root);
+        let file = OpenOptions::new()
             .write(true)
             .create(true)
-            .open(path_as)
-            .chain_err(|| format!("Could not create file {}", path_as.display()))
-    } else {
-        OpenOptions::new()
-            .read(true)
-            .write(true)
-            .open(path_as)
-            .chain_err(|| format!("Could not open file {}", path_as.display()))
+            .open(&lock_path)
+            .chain_err(|| format!("Could not open lock file {}", lock_path.display()))?;
+
+        lock_exclusive(&file)
+            .chain_err(|| format!("Could not lock {}", lock_path.display()))?;
+
+        Ok(StoreLock { _file: file })
     }
 }
 
+#[cfg(unix)]
+fn lock_exclusive(file: &File) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    // The lock is released when the file is closed.
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn lock_exclusive(_file: &File) -> io::Result<()> {
+    // TODO: Use LockFileEx on Windows.
+    Ok(())
+}
+
+/// Written at the start of every file oxidoc saves, so that files which weren't written by
+/// oxidoc can be detected.
+const FORMAT_MAGIC: &'static [u8] = b"OXIDOC";
+
+/// The version of the on-disk format. Bump this whenever the serialized layout of `Store`,
+/// `Documentation` or anything they contain changes. Files of other versions aren't migrated,
+/// since bincode can't read them without the old definitions of every type. Queries report that
+/// the documentation has to be regenerated instead, and generating refuses to touch the store
+/// until `oxidoc doctor --repair` has replaced it.
+pub const FORMAT_VERSION: u32 = 11;
+
+const HEADER_LEN: usize = 10;
+
+fn format_header() -> Vec<u8> {
+    let mut header = FORMAT_MAGIC.to_vec();
+    header.extend_from_slice(&[FORMAT_VERSION as u8,
+                               (FORMAT_VERSION >> 8) as u8,
+                               (FORMAT_VERSION >> 16) as u8,
+                               (FORMAT_VERSION >> 24) as u8]);
+    header
+}
+
+/// Returns the format version in the header at the start of `data`, read from `source`.
+fn read_format_version(data: &[u8], source: &str) -> Result<u32> {
+    if data.len() < HEADER_LEN || &data[..FORMAT_MAGIC.len()] != FORMAT_MAGIC {
+        bail!(ErrorKind::UnknownFormat(source.to_string()));
+    }
+
+    let v = &data[FORMAT_MAGIC.len()..HEADER_LEN];
+    Ok((v[0] as u32) | (v[1] as u32) << 8 | (v[2] as u32) << 16 | (v[3] as u32) << 24)
+}
+
+/// Checks the header of data read from `source`, returning the data without the header.
+fn strip_format_header<'a>(data: &'a [u8], source: &str) -> Result<&'a [u8]> {
+    let version = read_format_version(data, source)?;
+    if version != FORMAT_VERSION {
+        bail!(ErrorKind::IncompatibleFormat(source.to_string(), version, FORMAT_VERSION));
+    }
+
+    Ok(&data[HEADER_LEN..])
+}
+
+/// Serializes an object, prefixed with the format header.
+pub fn encode_object<S: Serialize>(data: &S) -> Result<Vec<u8>> {
+    let body = bincode::serialize(data, Infinite)
+        .chain_err(|| "Could not serialize data")?;
+
+    let mut encoded = format_header();
+    encoded.extend(body);
+    Ok(encoded)
+}
+
+/// Deserializes an object written by `encode_object`, read from `source`.
+pub fn decode_object<S: DeserializeOwned>(data: &[u8], source: &str) -> Result<S> {
+    let data = strip_format_header(data, source)?;
+    bincode::deserialize(data)
+        .chain_err(|| format!("Could not deserialize {}", source))
+}
+
 pub fn deserialize_object<S, T>(path: T) -> Result<S>
     where S: DeserializeOwned,
           T: AsRef<Path>
 {
     let path_as = path.as_ref();
-    let mut data: Vec<u8> = Vec::new();
-    let mut bincoded_file = File::open(&path_as)
-        .chain_err(|| format!("Could not open file {}", path_as.display()))?;
+    let source = path_as.display().to_string();
 
-    bincoded_file.read_to_end(&mut data)
-        .chain_err(|| format!("Failed to read file {}", path_as.display()))?;
-    let result = bincode::deserialize(data.as_slice())
-        .chain_err(|| format!("Could not deserialize file at {}", path_as.display()))?;
+    let mut data = Vec::new();
+    File::open(path_as)
+        .and_then(|mut file| file.read_to_end(&mut data))
+        .chain_err(|| format!("Failed to read file {}", source))?;
 
-    Ok(result)
+    decode_object(&data, &source)
 }
 
 pub fn serialize_object<S, T>(data: &S, path: T) -> Result<()>
@@ -57,14 +163,112 @@ pub fn serialize_object<S, T>(data: &S, path: T) -> Result<()>
 {
     let path_as = path.as_ref();
 
-    let data = bincode::serialize(data, Infinite)
+    let data = encode_object(data)
         .chain_err(|| format!("Could not serialize data for {}", path_as.display()))?;
 
-    let mut bincoded_file = create_or_open_file(path_as)?;
-    bincoded_file.write(data.as_slice())
-        .chain_err(|| format!("Failed to write file {}", path_as.display()))?;
+    backend::write_atomically(path_as, data.as_slice())
+}
+
+/// Writes documents into the archive `name` in `backend`, returning their locations. An archive
+/// is a format header and an ID followed by the bincoded documents, and is indexed by the
+/// offsets kept in the locations, so a single document can be read without loading the others.
+/// The ID is kept in the locations too, to detect archives which were regenerated after the
+/// store was loaded.
+fn write_archive(backend: &Arc<Backend>,
+                 name: &str,
+                 documents: &[Documentation]) -> Result<Vec<StoreLocation>> {
+    let archive_id = new_archive_id();
+    let mut data = format_header();
+    data.extend_from_slice(&u64_to_bytes(archive_id));
+    let mut locations = Vec::new();
 
-    Ok(())
+    for document in documents.iter() {
+        let record = bincode::serialize(document, Infinite)
+            .chain_err(|| format!("Could not serialize doc {}", document.mod_path))?;
+
+        let mut location = document.to_store_location();
+        location.offset = data.len() as u64;
+        location.length = record.len() as u64;
+        location.archive_id = archive_id;
+        location.backend = Some(backend.clone());
+        locations.push(location);
+
+        data.extend(record);
+    }
+
+    backend.write(name, data.as_slice())?;
+
+    Ok(locations)
+}
+
+/// Reads the document at `location` from its crate version's archive.
+pub fn read_archive_record(location: &StoreLocation) -> Result<Documentation> {
+    let backend = match location.backend {
+        Some(ref backend) => backend.clone(),
+        None              => backend::default_backend()?,
+    };
+    let name = location.archive_name()?;
+    let source = backend.describe(&name);
+
+    let header_len = (HEADER_LEN + 8) as u64;
+    let mut ranges = backend.read_ranges(&name, &[(0, header_len),
+                                                  (location.offset, location.length)])?;
+    let record = ranges.pop().unwrap();
+    let header = ranges.pop().unwrap();
+
+    if header.len() as u64 != header_len {
+        bail!(ErrorKind::UnknownFormat(source));
+    }
+
+    // Records can't be migrated one at a time, so archives have to be regenerated instead.
+    let version = read_format_version(&header, &source)?;
+    if version != FORMAT_VERSION {
+        bail!(ErrorKind::IncompatibleFormat(source, version, FORMAT_VERSION));
+    }
+
+    if u64_from_bytes(&header[HEADER_LEN..]) != location.archive_id {
+        bail!(ErrorKind::ArchiveChanged(source));
+    }
+
+    if record.len() as u64 != location.length {
+        bail!("{} is truncated, could not read {}", source, location.mod_path);
+    }
+
+    bincode::deserialize(record.as_slice())
+        .chain_err(|| format!("Could not deserialize {} from {}", location.mod_path, source))
+}
+
+/// Checks that `data` is an archive in the current format, read from `source`, and returns its
+/// ID.
+fn read_archive_id(data: &[u8], source: &str) -> Result<u64> {
+    let version = read_format_version(data, source)?;
+    if version != FORMAT_VERSION {
+        bail!(ErrorKind::IncompatibleFormat(source.to_string(), version, FORMAT_VERSION));
+    }
+
+    if data.len() < HEADER_LEN + 8 {
+        bail!(ErrorKind::UnknownFormat(source.to_string()));
+    }
+
+    Ok(u64_from_bytes(&data[HEADER_LEN..HEADER_LEN + 8]))
+}
+
+/// Returns a number which is different for every archive written.
+fn new_archive_id() -> u64 {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(Duration::from_secs(0));
+    (now.as_secs() << 30) ^ (now.subsec_nanos() as u64) ^ ((process::id() as u64) << 48)
+}
+
+fn u64_to_bytes(n: u64) -> [u8; 8] {
+    let mut bytes = [0; 8];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = (n >> (i * 8)) as u8;
+    }
+    bytes
+}
+
+fn u64_from_bytes(bytes: &[u8]) -> u64 {
+    bytes.iter().enumerate().fold(0, |n, (i, &byte)| n | (byte as u64) << (i * 8))
 }
 
 type CrateVersion = String;
@@ -76,71 +280,645 @@ type CrateVersions = HashMap<CrateVersion, Docset>;
 /// Top-level storage of all crates and their documents, organized by version.
 type DocumentCorpus = HashMap<CrateName, CrateVersions>;
 
-/// Mapping from module path keywords to the full module paths that use those keywords. Used for
-/// quick lookup of documentation based on keywords.
-type ModuleExpansions = HashMap<String, HashSet<String>>;
-
 /// The central point for retrieving documentation. Stores a map of crate names to their versions,
-/// which map to their individual documentation stores. Also contains a keyword prefix map for
+/// which map to their individual documentation stores. Also contains an index of module paths for
 /// quick documentation searching.
 #[derive(Serialize, Deserialize)]
 pub struct Store {
     /// "serde" => "1.0.0" => Docset { /* ... */}
     items: DocumentCorpus,
 
-    /// A map from individual module path segments to fully resolved module paths that use them.
-    /// "vec" => ["std::vec::Vec", ...]
-    module_expansions: ModuleExpansions,
+    /// The index of the module paths of all documents, built when the store is saved. `None` if
+    /// documentation was added or removed since, in which case searches build it on the fly.
+    path_index: Option<PathIndex>,
+
+    /// True if this store merges several stores for searching, and can't be saved.
+    #[serde(skip_serializing, skip_deserializing)]
+    layered: bool,
+
+    /// The backends of the stores merged into this one, in order of precedence.
+    #[serde(skip_serializing, skip_deserializing)]
+    layers: Vec<Arc<Backend>>,
+
+    /// The full-text index, which is kept in its own file and only loaded when needed.
+    #[serde(skip_serializing, skip_deserializing)]
+    text_index: Option<TextIndex>,
+
+    /// The signatures of functions and methods, which are kept in their own file and only
+    /// loaded when needed, like the full-text index.
+    #[serde(skip_serializing, skip_deserializing)]
+    signature_index: Option<SignatureIndex>,
+
+    /// Where the store and its documentation are kept. `None` stands for the writable store.
+    #[serde(skip_serializing, skip_deserializing)]
+    backend: Option<Arc<Backend>>,
+
+    /// How results of searches by name are ordered.
+    #[serde(skip_serializing, skip_deserializing)]
+    ranking: Ranking,
 }
 
 impl Store {
     pub fn new() -> Self {
         Store {
             items: HashMap::new(),
-            module_expansions: HashMap::new(),
+            path_index: None,
+            layered: false,
+            layers: Vec::new(),
+            text_index: None,
+            signature_index: None,
+            backend: None,
+            ranking: Ranking::new(),
+        }
+    }
+
+    /// Creates an empty store kept in `backend`.
+    pub fn with_backend(backend: Arc<Backend>) -> Self {
+        let mut store = Store::new();
+        store.backend = Some(backend);
+        store
+    }
+
+    fn backend(&self) -> Result<Arc<Backend>> {
+        match self.backend {
+            Some(ref backend) => Ok(backend.clone()),
+            None              => backend::default_backend(),
+        }
+    }
+
+    /// Loads the writable store, or returns an empty one if no documentation has been generated
+    /// yet.
+    pub fn load() -> Result<Self> {
+        Store::load_from(backend::default_backend()?)
+    }
+
+    /// Loads the store kept in `backend`, or returns an empty one if there is none.
+    pub fn load_from(backend: Arc<Backend>) -> Result<Self> {
+        if !backend.exists(STORE_NAME) {
+            return Ok(Store::with_backend(backend));
+        }
+
+        let data = backend.read(STORE_NAME)?;
+        let mut store: Store = decode_object(&data, &backend.describe(STORE_NAME))?;
+        store.set_backend(backend);
+
+        Ok(store)
+    }
+
+    /// Loads all stores searched by queries, the project-local, writable and shared stores, and
+    /// merges them. If a crate version is in several stores, the documentation of the first one
+    /// is used. Stores that can't be read are skipped. The result can't be saved.
+    pub fn load_layered() -> Result<Self> {
+        let mut store = Store::new();
+        store.layered = true;
+        store.ranking = Ranking::for_current_project();
+
+        for root in paths::store_roots()? {
+            let backend: Arc<Backend> = Arc::new(FsBackend::new(root.clone()));
+            let layer = match Store::load_from(backend.clone()) {
+                Ok(layer) => layer,
+                Err(e) => {
+                    eprintln!("Skipping documentation store {}: {}", root.display(), e);
+                    continue;
+                }
+            };
+
+            store.layers.push(backend);
+            store.merge(layer);
+        }
+
+        if store.path_index.is_none() {
+            store.build_path_index();
+        }
+
+        Ok(store)
+    }
+
+    /// Changes how the results of searches by name are ordered.
+    pub fn set_ranking(&mut self, ranking: Ranking) {
+        self.ranking = ranking;
+    }
+
+    /// Makes the documents of this store refer to `backend`.
+    fn set_backend(&mut self, backend: Arc<Backend>) {
+        for krate in self.items.values_mut() {
+            for docset in krate.values_mut() {
+                for location in docset.documents.values_mut() {
+                    location.backend = Some(backend.clone());
+                }
+            }
         }
+        self.backend = Some(backend);
+    }
+
+    /// Adds the crate versions of `other` which aren't in this store yet.
+    fn merge(&mut self, other: Store) {
+        // The index of the first store merged is still up to date.
+        self.path_index = if self.items.is_empty() { other.path_index } else { None };
+
+        for (krate_name, versions) in other.items {
+            let mut entry = self.items.entry(krate_name).or_insert(HashMap::new());
+            for (version, docset) in versions {
+                entry.entry(version).or_insert(docset);
+            }
+        }
+    }
+
+    /// Indexes the module paths of all documents, so searches don't have to do it.
+    fn build_path_index(&mut self) {
+        self.path_index = Some(PathIndex::build(self.all_mod_paths()));
+    }
+
+    fn all_mod_paths(&self) -> Vec<String> {
+        self.items.values()
+            .flat_map(|versions| versions.values())
+            .flat_map(|docset| docset.documents.values())
+            .map(|location| location.mod_path.to_string())
+            .collect()
     }
 
-    pub fn load() -> Self {
-        match Store::load_from_disk() {
-            Ok(store) => store,
-            Err(_)    => Store::new(),
+    /// Returns the index of the module paths, building it if it isn't up to date.
+    fn path_index(&self) -> Cow<PathIndex> {
+        match self.path_index {
+            Some(ref index) => Cow::Borrowed(index),
+            None            => Cow::Owned(PathIndex::build(self.all_mod_paths())),
         }
     }
 
+    /// Loads the store to add newly generated documentation to it. A store written by an
+    /// incompatible version of oxidoc isn't replaced, since the documentation of every crate in
+    /// it would be lost. `IncompatibleStore` is returned instead, with the crate versions found
+    /// in the backend, until the store is replaced with `replace_incompatible`.
+    pub fn load_for_generation() -> Result<Self> {
+        let backend = backend::default_backend()?;
+        match Store::load_from(backend.clone()) {
+            Err(Error(ErrorKind::UnknownFormat(..), _)) |
+            Err(Error(ErrorKind::IncompatibleFormat(..), _)) => {
+                bail!(ErrorKind::IncompatibleStore(backend.describe(STORE_NAME),
+                                                   archived_crate_versions(&*backend)?))
+            },
+            result => result,
+        }
+    }
+
+    /// Replaces a store written by an incompatible version of oxidoc with an empty one, deleting
+    /// its files. Returns the crate versions whose documentation was dropped. The `StoreLock` has
+    /// to be held while calling this.
+    pub fn replace_incompatible() -> Result<(Self, Vec<String>)> {
+        let backend = backend::default_backend()?;
+        let dropped = archived_crate_versions(&*backend)?;
+
+        let mut store = Store::with_backend(backend);
+        store.clear()?;
+
+        Ok((store, dropped))
+    }
+
+    /// Removes all documentation from the store and deletes the files oxidoc wrote into its
+    /// backend, including orphaned archives. Other files in the store root, like the lock file,
+    /// are left alone, and so are directories, since crate directories of old versions of oxidoc
+    /// can only be told apart by their names. The `StoreLock` has to be held while calling this.
+    pub fn clear(&mut self) -> Result<()> {
+        if self.layered {
+            bail!("A merged view of several documentation stores can't be cleared");
+        }
+
+        let backend = self.backend()?;
+        for name in backend.list()? {
+            let is_store_file = !backend.is_dir(&name) &&
+                (name == STORE_NAME || name == TEXT_INDEX_NAME || name == SIGNATURE_INDEX_NAME ||
+                 paths::is_archive_name(&name));
+
+            if is_store_file {
+                backend.remove(&name)?;
+            }
+        }
+
+        self.items.clear();
+        self.path_index = None;
+        self.text_index = Some(TextIndex::new());
+        self.signature_index = Some(SignatureIndex::new());
+
+        Ok(())
+    }
+
+    /// Saves the store to its backend.
     pub fn save(&mut self) -> Result<()> {
-        let store_file = paths::store_file_path()?;
-        serialize_object(self, store_file)
+        if self.layered {
+            bail!("A merged view of several documentation stores can't be saved");
+        }
+
+        let backend = self.backend()?;
+
+        if let Some(ref text_index) = self.text_index {
+            text_index.save_to(&backend)?;
+        }
+
+        if let Some(ref signature_index) = self.signature_index {
+            signature_index.save_to(&backend)?;
+        }
+
+        if self.path_index.is_none() {
+            self.build_path_index();
+        }
+
+        let data = encode_object(self)
+            .chain_err(|| format!("Could not serialize {}", backend.describe(STORE_NAME)))?;
+
+        backend.write(STORE_NAME, data.as_slice())
+    }
+
+    /// Loads the full-text index of the store, or of every layer of a merged store, so it can be
+    /// searched with `search_text`.
+    pub fn load_text_index(&mut self) -> Result<()> {
+        if self.text_index.is_some() {
+            return Ok(());
+        }
+
+        let mut text_index = TextIndex::new();
+        if self.layered {
+            for backend in self.layers.iter() {
+                match TextIndex::load_from(backend) {
+                    Ok(layer) => text_index.merge(layer),
+                    Err(e) => eprintln!("Skipping full-text index {}: {}",
+                                        backend.describe(TEXT_INDEX_NAME), e),
+                }
+            }
+        } else {
+            text_index = TextIndex::load_from(&self.backend()?)?;
+        }
+
+        self.text_index = Some(text_index);
+        Ok(())
     }
 
-    pub fn load_from_disk() -> Result<Self> {
-        let store_file = paths::store_file_path()?;
-        deserialize_object(store_file)
+    /// Returns the full-text index to update it, loading it first if needed. An index written by
+    /// an incompatible version of oxidoc is replaced with an empty one.
+    fn text_index_mut(&mut self) -> Result<&mut TextIndex> {
+        if self.text_index.is_none() {
+            let text_index = match TextIndex::load_from(&self.backend()?) {
+                Err(Error(ErrorKind::UnknownFormat(..), _)) |
+                Err(Error(ErrorKind::IncompatibleFormat(..), _)) => TextIndex::new(),
+                result => result?,
+            };
+            self.text_index = Some(text_index);
+        }
+
+        Ok(self.text_index.as_mut().unwrap())
+    }
+
+    /// Loads the signature index of the store, or of every layer of a merged store, so it can be
+    /// searched with `lookup_signature`.
+    pub fn load_signature_index(&mut self) -> Result<()> {
+        if self.signature_index.is_some() {
+            return Ok(());
+        }
+
+        let mut signature_index = SignatureIndex::new();
+        if self.layered {
+            for backend in self.layers.iter() {
+                match SignatureIndex::load_from(backend) {
+                    Ok(layer) => signature_index.merge(layer),
+                    Err(e) => eprintln!("Skipping signature index {}: {}",
+                                        backend.describe(SIGNATURE_INDEX_NAME), e),
+                }
+            }
+        } else {
+            signature_index = SignatureIndex::load_from(&self.backend()?)?;
+        }
+
+        self.signature_index = Some(signature_index);
+        Ok(())
+    }
+
+    /// Returns the signature index to update it, loading it first if needed, like
+    /// `text_index_mut`.
+    fn signature_index_mut(&mut self) -> Result<&mut SignatureIndex> {
+        if self.signature_index.is_none() {
+            let signature_index = match SignatureIndex::load_from(&self.backend()?) {
+                Err(Error(ErrorKind::UnknownFormat(..), _)) |
+                Err(Error(ErrorKind::IncompatibleFormat(..), _)) => SignatureIndex::new(),
+                result => result?,
+            };
+            self.signature_index = Some(signature_index);
+        }
+
+        Ok(self.signature_index.as_mut().unwrap())
+    }
+
+    /// Writes the documentation for a crate version to the store's backend and adds it.
+    pub fn add_documents(&mut self, crate_info: CrateInfo, documents: Vec<Documentation>) -> Result<()> {
+        let text_index = DocsetTextIndex::from_documents(&documents);
+        let signatures = DocsetSignatures::from_documents(&documents);
+        let docset = Docset::from_documents(&self.backend()?, documents)?;
+        self.text_index_mut()?.insert(&crate_info, text_index);
+        self.signature_index_mut()?.insert(&crate_info, signatures);
+        self.add_docset(crate_info, docset);
+        Ok(())
     }
 
     /// Add documentation for a specific version of a crate.
     pub fn add_docset(&mut self, crate_info: CrateInfo, docset: Docset) {
-        // TODO: Any way to remove old module expansions if docset is regenerated?
-        for doc in docset.documents.values() {
-            self.add_module_expansions(doc);
+        {
+            let mut entry = self.items.entry(crate_info.name).or_insert(HashMap::new());
+            entry.insert(crate_info.version, docset);
+        }
+
+        self.path_index = None;
+    }
+
+    /// Packs the documentation for a crate version into a bundle, which can be imported into
+    /// another store. Without a version, or with a version requirement like `0.1`, the newest
+    /// matching version is exported.
+    pub fn export_bundle(&self, krate_name: &str, version: Option<&str>) -> Result<Bundle> {
+        let spec = match version {
+            Some(version) => format!("{}@{}", krate_name, version),
+            None          => krate_name.to_string(),
+        };
+
+        let (version, docset) = match self.docset(krate_name, version) {
+            Some(found) => found,
+            None        => bail!(ErrorKind::NoSuchCrate(spec)),
+        };
+
+        let crate_info = CrateInfo {
+            name: krate_name.to_string(),
+            version: version.to_string(),
+            lib_path: None,
+        };
+
+        // The documents may come from any of the stores merged into this one.
+        let archive = match docset.documents.values().next() {
+            Some(location) => {
+                let backend = match location.backend {
+                    Some(ref backend) => backend.clone(),
+                    None              => self.backend()?,
+                };
+                backend.read(&location.archive_name()?)?
+            },
+            None => Vec::new(),
+        };
+
+        let text_index = self.text_index.as_ref()
+            .and_then(|index| index.get(&crate_info.name, &crate_info.version))
+            .cloned();
+        let signatures = self.signature_index.as_ref()
+            .and_then(|index| index.get(&crate_info.name, &crate_info.version))
+            .cloned();
+
+        Ok(Bundle::new(crate_info, docset.clone(), archive, text_index, signatures))
+    }
+
+    /// Adds the crate version in `bundle` to the store, writing its documentation archive to the
+    /// store's backend. Documentation already in the store for the same crate version is
+    /// replaced. Returns the imported crate version.
+    pub fn import_bundle(&mut self, bundle: Bundle) -> Result<CrateInfo> {
+        let Bundle { crate_info, mut docset, archive, text_index, signatures, .. } = bundle;
+        let backend = self.backend()?;
+
+        if !docset.documents.is_empty() {
+            let source = format!("the bundle for {}", crate_info);
+            let archive_id = read_archive_id(&archive, &source)?;
+
+            let is_consistent = docset.documents.values().all(|location| {
+                location.archive_id == archive_id &&
+                    location.crate_info.name == crate_info.name &&
+                    location.crate_info.version == crate_info.version
+            });
+            if !is_consistent {
+                bail!("The documentation index in {} doesn't match its archive", source);
+            }
+
+            backend.write(&paths::crate_archive_name(&crate_info)?, archive.as_slice())?;
+        }
+
+        for location in docset.documents.values_mut() {
+            location.backend = Some(backend.clone());
+        }
+
+        self.text_index_mut()?.insert(&crate_info, text_index.unwrap_or_default());
+        self.signature_index_mut()?.insert(&crate_info, signatures.unwrap_or_default());
+        self.add_docset(crate_info.clone(), docset);
+
+        Ok(crate_info)
+    }
+
+    /// Removes all versions of a crate from the store and deletes their documentation files.
+    /// Returns the removed crate versions.
+    pub fn remove_crate(&mut self, krate_name: &str) -> Result<Vec<CrateInfo>> {
+        let versions: Vec<CrateVersion> = match self.items.get(krate_name) {
+            Some(versions) => versions.keys().cloned().collect(),
+            None           => bail!(ErrorKind::NoSuchCrate(krate_name.to_string())),
+        };
+
+        self.remove_versions(krate_name, versions)
+    }
+
+    /// Removes a single version of a crate from the store and deletes its documentation files.
+    pub fn remove_version(&mut self, krate_name: &str, version: &str) -> Result<CrateInfo> {
+        let exists = self.items.get(krate_name).map_or(false, |v| v.contains_key(version));
+        if !exists {
+            bail!(ErrorKind::NoSuchCrate(format!("{}@{}", krate_name, version)));
+        }
+
+        let mut removed = self.remove_versions(krate_name, vec![version.to_string()])?;
+        Ok(removed.remove(0))
+    }
+
+    /// Removes all but the newest `keep` versions of a crate. Returns the removed crate versions.
+    pub fn retain_newest_versions(&mut self, krate_name: &str, keep: usize) -> Result<Vec<CrateInfo>> {
+        let mut versions: Vec<CrateVersion> = match self.items.get(krate_name) {
+            Some(versions) => versions.keys().cloned().collect(),
+            None           => bail!(ErrorKind::NoSuchCrate(krate_name.to_string())),
+        };
+
+        versions.sort_by(|a, b| compare_versions(b, a));
+        let old_versions = versions.into_iter().skip(keep).collect();
+
+        self.remove_versions(krate_name, old_versions)
+    }
+
+    /// Returns the names of all crates in the store.
+    pub fn crate_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.items.keys().map(|k| k.as_str()).collect();
+        names.sort();
+        names
+    }
+
+    /// Returns the versions of a crate in the store, newest first.
+    pub fn crate_versions(&self, krate_name: &str) -> Vec<&str> {
+        match self.items.get(krate_name) {
+            Some(versions) => matching_versions(versions, None).into_iter().map(|v| v.as_str()).collect(),
+            None           => Vec::new(),
         }
+    }
 
-        let mut entry = self.items.entry(crate_info.name).or_insert(HashMap::new());
-        entry.insert(crate_info.version, docset);
+    /// Returns the documentation for a version of a crate, along with the version. The version
+    /// may be exact or a requirement like `0.1`, in which case the newest matching version is
+    /// used. Without a version, the newest version is used.
+    pub fn docset(&self, krate_name: &str, version: Option<&str>) -> Option<(&str, &Docset)> {
+        self.items.get(krate_name).and_then(|versions| {
+            matching_versions(versions, version)
+                .into_iter()
+                .next()
+                .map(|v| (v.as_str(), &versions[v]))
+        })
     }
 
-    /// Adds the keywords for module paths in the provided document to the prefix map used for
-    /// document loookup.
-    fn add_module_expansions(&mut self, doc: &StoreLocation) {
-        for segment in doc.mod_path.0.iter() {
-            let mod_path = doc.mod_path.to_string().to_lowercase();
+    /// Returns the documents directly below `path`, like the items of a module or the methods
+    /// of a type, from the crate version chosen like in `docset`.
+    pub fn children(&self,
+                    path: &ModPath,
+                    version: Option<&str>,
+                    include_private: bool) -> Vec<&StoreLocation> {
+        self.docset_for_path(path, version)
+            .map_or(Vec::new(), |docset| docset.children(path, include_private))
+    }
 
-            let entry = self.module_expansions
-                .entry(segment.identifier.to_lowercase())
-                .or_insert(HashSet::new());
+    /// Returns everything below `path` in depth-first order, along with the depth of each
+    /// document below `path`, starting at 0. The crate version is chosen like in `docset`.
+    pub fn tree(&self,
+                path: &ModPath,
+                version: Option<&str>,
+                include_private: bool) -> Vec<(usize, &StoreLocation)> {
+        self.docset_for_path(path, version)
+            .map_or(Vec::new(), |docset| docset.tree(path, include_private))
+    }
+
+    fn docset_for_path(&self, path: &ModPath, version: Option<&str>) -> Option<&Docset> {
+        path.head()
+            .and_then(|krate| self.docset(&krate.identifier.to_lowercase(), version))
+            .map(|(_, docset)| docset)
+    }
+
+    fn remove_versions(&mut self,
+                       krate_name: &str,
+                       versions: Vec<CrateVersion>) -> Result<Vec<CrateInfo>> {
+        let mut removed = Vec::new();
+
+        if let Some(krate_versions) = self.items.get_mut(krate_name) {
+            for version in versions {
+                if krate_versions.remove(&version).is_some() {
+                    removed.push(CrateInfo {
+                        name: krate_name.to_string(),
+                        version: version,
+                        lib_path: None,
+                    });
+                }
+            }
+        }
 
-            entry.insert(mod_path);
+        let is_empty = self.items.get(krate_name).map_or(false, |v| v.is_empty());
+        if is_empty {
+            self.items.remove(krate_name);
         }
+
+        self.path_index = None;
+
+        for crate_info in removed.iter() {
+            self.text_index_mut()?.remove(&crate_info.name, &crate_info.version);
+            self.signature_index_mut()?.remove(&crate_info.name, &crate_info.version);
+        }
+
+        let backend = self.backend()?;
+        for crate_info in removed.iter() {
+            let archive_name = paths::crate_archive_name(crate_info)?;
+            if backend.exists(&archive_name) {
+                backend.remove(&archive_name)?;
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// Checks that every document in the store can be loaded, and finds documentation files in
+    /// its backend which aren't referenced by the store anymore. Only files oxidoc writes are
+    /// considered, since the store root may be shared with other files.
+    pub fn check_integrity(&self) -> Result<IntegrityReport> {
+        let backend = self.backend()?;
+        let mut report = IntegrityReport::new();
+        let mut archives = HashSet::new();
+
+        for (krate_name, krate) in self.items.iter() {
+            for (version, docset) in krate.iter() {
+                let crate_info = CrateInfo {
+                    name: krate_name.clone(),
+                    version: version.clone(),
+                    lib_path: None,
+                };
+                let archive_name = paths::crate_archive_name(&crate_info)?;
+                let archive_exists = backend.exists(&archive_name);
+                archives.insert(archive_name);
+
+                for location in docset.documents.values() {
+                    report.checked += 1;
+
+                    if !archive_exists {
+                        report.missing.push(location.clone());
+                    } else if let Err(e) = read_archive_record(location) {
+                        report.corrupt.push((location.clone(), e.to_string()));
+                    }
+                }
+            }
+        }
+
+        for name in backend.list()? {
+            let is_orphaned = if backend.is_dir(&name) {
+                if paths::is_legacy_crate_dir_name(&name) {
+                    report.legacy_dirs.push(name);
+                }
+                continue;
+            } else if paths::is_archive_name(&name) {
+                !archives.contains(&name)
+            } else {
+                // Temporary files of interrupted writers. Those of running writers are kept.
+                backend::is_stale_temp_file(&name)
+            };
+
+            if is_orphaned {
+                report.orphaned.push(name);
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Removes the missing and corrupt documents found by `check_integrity` from the store, and
+    /// deletes the orphaned files and the legacy crate directories holding only documentation.
+    /// Returns the legacy directories which were skipped because they contain other files.
+    pub fn repair(&mut self, report: &IntegrityReport) -> Result<Vec<String>> {
+        let broken = report.missing.iter().chain(report.corrupt.iter().map(|&(ref loc, _)| loc));
+
+        for location in broken {
+            if let Some(docset) = self.items.get_mut(&location.crate_info.name)
+                .and_then(|versions| versions.get_mut(&location.crate_info.version)) {
+                docset.remove(&location.key());
+            }
+
+            self.text_index_mut()?.remove_document(&location.crate_info.name,
+                                                   &location.crate_info.version,
+                                                   &location.key());
+            self.signature_index_mut()?.remove_document(&location.crate_info.name,
+                                                        &location.crate_info.version,
+                                                        &location.key());
+        }
+
+        let backend = self.backend()?;
+        for name in report.orphaned.iter() {
+            backend.remove(name)?;
+        }
+
+        let mut skipped = Vec::new();
+        for name in report.legacy_dirs.iter() {
+            match backend.remove(name) {
+                Err(Error(ErrorKind::ForeignFiles(..), _)) => skipped.push(name.clone()),
+                result => result?,
+            }
+        }
+
+        self.path_index = None;
+
+        Ok(skipped)
     }
 
     pub fn all_locations(&self) -> Vec<StoreLocation> {
@@ -154,142 +932,630 @@ impl Store {
     }
 
     /// Search the documentation store for a keyword and return the documents with a match inside
-    /// their module paths.
+    /// their module paths. Private and hidden items are excluded.
     pub fn lookup_name(&self, query: &str) -> Vec<&StoreLocation> {
+        self.lookup_filtered(query, &SearchFilter::new())
+    }
+
+    /// Like `lookup_name`, but also returns private and `#[doc(hidden)]` items.
+    pub fn lookup_name_including_private(&self, query: &str) -> Vec<&StoreLocation> {
+        let mut filter = SearchFilter::new();
+        filter.include_private = true;
+        self.lookup_filtered(query, &filter)
+    }
+
+    /// Like `lookup_name`, but only returns the items passing `filter`. Prefixes of the query
+    /// like `fn:` or `unsafe:` restrict the results further.
+    pub fn lookup_filtered(&self, query: &str, filter: &SearchFilter) -> Vec<&StoreLocation> {
         let mut results = Vec::new();
+        let mut filter = filter.clone();
+        let query = VersionedQuery::parse(filter.parse_prefixes(query));
+        let segments = search::query_segments(&query.path);
 
-        let matches = get_all_matching_paths(query.to_string(), &self.module_expansions);
+        let path_index = self.path_index();
+        let matches = path_index.matching_paths(&segments);
 
         for mat in matches {
-            if let Some(loc) = self.retrieve_match(mat) {
-                results.push(loc);
+            let krate_name = mat.split("::").next().unwrap_or("").to_string();
+            let krates = query.krate.iter().chain(filter.krate.iter());
+            if krates.map(|krate| krate.to_lowercase()).any(|krate| krate != krate_name) {
+                continue;
             }
-        }
 
-        results.sort_by_key(|loc| levenshtein(query, &loc.mod_path.to_string()));
+            let version = query.version.as_ref().or(filter.version.as_ref()).map(|v| v.as_str());
 
+            for loc in self.retrieve_match(mat.to_string(), version) {
+                if filter.matches(loc) {
+                    results.push(loc);
+                }
+            }
+        }
+
+        self.ranking.sort(&query.path, &mut results);
         results
     }
 
-    /// Searches the documentation store for the given fully resolved module path string.
-    fn retrieve_match(&self, mat: String) -> Option<&StoreLocation> {
+    /// Completes a module path or an item name, ignoring case, returning at most `limit` full
+    /// paths with the shortest first. A prefix like `std::coll` is completed as a path, and one
+    /// without `::` like `HashM` as the name of an item anywhere. Private and hidden items are
+    /// left out.
+    pub fn complete(&self, prefix: &str, limit: usize) -> Vec<String> {
+        let path_index = self.path_index();
+        let candidates: Vec<&str> = if prefix.contains("::") {
+            let mut paths: Vec<&str> = path_index.paths_with_prefix(prefix)
+                .iter()
+                .map(|p| p.as_str())
+                .collect();
+            paths.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+            paths
+        } else {
+            path_index.paths_with_name_prefix(prefix)
+        };
+
+        // The index only has lowercased paths, so the paths are taken from the documents.
+        let mut completions: Vec<String> = Vec::new();
+        for path in candidates {
+            for location in self.retrieve_match(path.to_string(), None) {
+                if location.is_private {
+                    continue;
+                }
+                let completion = location.mod_path.to_string();
+                if !completions.contains(&completion) {
+                    completions.push(completion);
+                }
+            }
+            if completions.len() >= limit {
+                break;
+            }
+        }
+
+        completions.truncate(limit);
+        completions
+    }
+
+    /// Returns the crate versions searched by a query with `filter`, which is the newest version
+    /// of each crate matching the crate and version of the filter.
+    fn filtered_docsets<'a>(&'a self, filter: &SearchFilter) -> Vec<(&'a str, &'a str, &'a Docset)> {
+        let krate = filter.krate.as_ref().map(|krate| krate.to_lowercase());
+        let version = filter.version.as_ref().map(|v| v.as_str());
+
+        let mut docsets = Vec::new();
+        for (krate_name, versions) in self.items.iter() {
+            if krate.as_ref().map_or(false, |krate| *krate != krate_name.to_lowercase()) {
+                continue;
+            }
+
+            if let Some(v) = matching_versions(versions, version).into_iter().next() {
+                docsets.push((krate_name.as_str(), v.as_str(), &versions[v]));
+            }
+        }
+        docsets
+    }
+
+    /// Searches the text of the documentation of the newest version of each crate matching
+    /// `filter`, returning the matching documents which pass the filter with the most relevant
+    /// first, along with their BM25 scores. `load_text_index` has to be called first.
+    pub fn search_text(&self, query: &str, filter: &SearchFilter) -> Result<Vec<(f64, &StoreLocation)>> {
+        let text_index = match self.text_index {
+            Some(ref text_index) => text_index,
+            None                 => bail!("The full-text index has not been loaded"),
+        };
+
+        let mut docsets = Vec::new();
+        let mut indexes = Vec::new();
+        for (krate_name, version, docset) in self.filtered_docsets(filter) {
+            if let Some(index) = text_index.get(krate_name, version) {
+                docsets.push(docset);
+                indexes.push(index);
+            }
+        }
+
+        let mut results: Vec<(f64, &StoreLocation)> = text_search::score_documents(&indexes, query)
+            .into_iter()
+            .filter_map(|((i, key), score)| docsets[i].documents.get(&key).map(|loc| (score, loc)))
+            .filter(|&(_, loc)| filter.matches(loc))
+            .collect();
+
+        results.sort_by(|a, b| {
+            b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal)
+                .then_with(|| a.1.mod_path.to_string().cmp(&b.1.mod_path.to_string()))
+        });
+
+        Ok(results)
+    }
+
+    /// Searches the functions and methods of the newest version of each crate matching `filter`
+    /// by type signature, like `&str -> Result<u32, _>`, returning the matching documents which
+    /// pass the filter with the closest first, along with the cost of matching them. See
+    /// `SignatureQuery::parse` for the syntax. `load_signature_index` has to be called first.
+    pub fn lookup_signature(&self, query: &str, filter: &SearchFilter) -> Result<Vec<(u32, &StoreLocation)>> {
+        let signature_index = match self.signature_index {
+            Some(ref signature_index) => signature_index,
+            None                      => bail!("The signature index has not been loaded"),
+        };
+        let query = SignatureQuery::parse(query)?;
+
+        let mut results = Vec::new();
+        for (krate_name, version, docset) in self.filtered_docsets(filter) {
+            let signatures = match signature_index.get(krate_name, version) {
+                Some(signatures) => &signatures.signatures,
+                None             => continue,
+            };
+
+            for (key, signature) in signatures.iter() {
+                let location = match docset.documents.get(key) {
+                    Some(location) => location,
+                    None           => continue,
+                };
+                if !filter.matches(location) {
+                    continue;
+                }
+
+                if let Some(cost) = query.match_cost(signature) {
+                    results.push((cost, location));
+                }
+            }
+        }
+
+        results.sort_by(|a, b| {
+            a.0.cmp(&b.0)
+                .then_with(|| a.1.mod_path.0.len().cmp(&b.1.mod_path.0.len()))
+                .then_with(|| a.1.mod_path.to_string().cmp(&b.1.mod_path.to_string()))
+        });
+
+        Ok(results)
+    }
+
+    /// Finds the document `location` refers to in this store, which may have been loaded after
+    /// `location` was, so that it can be read again once its archive was regenerated.
+    pub fn relocate(&self, location: &StoreLocation) -> Option<&StoreLocation> {
+        self.items.get(&location.crate_info.name)
+            .and_then(|versions| versions.get(&location.crate_info.version))
+            .and_then(|docset| docset.documents.get(&location.key()))
+    }
+
+    /// Retrieves the documentation for a fully resolved module path, like `std::vec::Vec`. If
+    /// several kinds of items have the path, types are preferred.
+    pub fn lookup_path(&self, path: &ModPath) -> Option<&StoreLocation> {
+        let mut matches: Vec<&StoreLocation> = self.retrieve_match(path.to_string().to_lowercase(), None)
+            .into_iter()
+            .filter(|loc| loc.mod_path == *path)
+            .collect();
+
+        matches.sort_by_key(|loc| match loc.doc_type {
+            DocType::Struct | DocType::Enum | DocType::Trait | DocType::Primitive => 0,
+            _ => 1,
+        });
+        matches.into_iter().next()
+    }
+
+    /// Finds the documentation for a type given its full path, or the type's name if the path
+    /// could not be resolved when the documentation was generated.
+    pub fn lookup_type(&self, path: &ModPath) -> Option<&StoreLocation> {
+        if let Some(location) = self.lookup_path(path) {
+            return Some(location);
+        }
+
+        let name = match path.name() {
+            Some(segment) => segment.identifier,
+            None          => return None,
+        };
+
+        self.lookup_name(&name).into_iter().find(|loc| {
+            loc.name == name && match loc.doc_type {
+                DocType::Struct | DocType::Enum | DocType::Trait | DocType::Primitive => true,
+                _ => false,
+            }
+        })
+    }
+
+    /// Returns the type that the documented type dereferences to, if it implements `Deref`.
+    pub fn deref_target(&self, location: &StoreLocation) -> Option<&ModPath> {
+        self.items.get(&location.crate_info.name)
+            .and_then(|versions| versions.get(&location.crate_info.version))
+            .and_then(|docset| docset.deref_targets.get(&location.key()))
+    }
+
+    /// Looks up a `Type::item` query through the `Deref` target of `Type`, for items that aren't
+    /// defined on the type itself, like `String::trim`. Returns the target along with the
+    /// matches found on it.
+    pub fn lookup_through_deref(&self, query: &str) -> Option<(ModPath, Vec<&StoreLocation>)> {
+        let mut segments: Vec<&str> = query.split("::").collect();
+        if segments.len() < 2 {
+            return None;
+        }
+
+        let item = segments.pop().unwrap();
+        let type_query = segments.join("::");
+
+        for location in self.lookup_name(&type_query) {
+            if let Some(target) = self.deref_target(location) {
+                let target_type = match self.lookup_type(target) {
+                    Some(t) => t,
+                    None    => continue,
+                };
+
+                let target_query = format!("{}::{}", target_type.mod_path, item);
+                let results: Vec<&StoreLocation> = self.lookup_name(&target_query)
+                    .into_iter()
+                    .filter(|loc| loc.mod_path.parent().as_ref() == Some(&target_type.mod_path))
+                    .collect();
+
+                if !results.is_empty() {
+                    return Some((target.clone(), results));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Returns the newest version of a crate in the store.
+    pub fn newest_version(&self, krate_name: &str) -> Option<&str> {
+        self.items.get(krate_name)
+            .and_then(|versions| latest_version(versions))
+            .map(|v| v.as_str())
+    }
+
+    /// Searches the documentation store for the given fully resolved module path string, in the
+    /// crate versions matching `version` or in all versions if it's `None`. The path is matched
+    /// case-insensitively, so several items of different kinds may be returned.
+    fn retrieve_match(&self, mat: String, version: Option<&str>) -> Vec<&StoreLocation> {
         let krate_name = mat.split("::").next().unwrap().to_string();
 
         let path_in_krate = ModPath::from(mat.clone());
-        self.latest_doc_with_match(&krate_name, path_in_krate)
+        self.newest_docs_with_match(&krate_name, path_in_krate, version)
     }
 
-    /// Retrieves the latest documentation for a crate matching the given module path
-    fn latest_doc_with_match(&self, krate_name: &str, path_in_krate: ModPath) -> Option<&StoreLocation> {
-        // FIXME: Doesn't handle items that exist in old versions and removed in the latest version
+    /// Retrieves the documentation matching the given module path from the newest crate version
+    /// which contains it, so items removed in later versions can still be found.
+    fn newest_docs_with_match(&self,
+                              krate_name: &str,
+                              path_in_krate: ModPath,
+                              version: Option<&str>) -> Vec<&StoreLocation> {
         if let Some(krate_versions) = self.items.get(krate_name) {
-            if let Some(version) = latest_version(krate_versions) {
-                krate_versions.get(version).and_then(|docset| {
-                    let path = path_in_krate.tail().to_string();
-                    docset.documents.get(&path)
-                })
-            } else {
-                None
-            }
+            let path = path_in_krate.tail().to_string();
+
+            matching_versions(krate_versions, version)
+                .into_iter()
+                .filter_map(|v| krate_versions.get(v))
+                .map(|docset| docset.find(&path))
+                .find(|locations| !locations.is_empty())
+                .unwrap_or(Vec::new())
         } else {
-            None
+            Vec::new()
         }
     }
 }
 
-fn latest_version(versions: &CrateVersions) -> Option<&CrateVersion> {
-    let mut max = None;
-    let mut res = None;
-    for version in versions.keys() {
-        let hash = version_number_hash(version);
-        if max.map_or(true, |m| hash > m) {
-            res = Some(version);
-            max = Some(hash);
-        }
-    }
-    res
+/// A search query which may be restricted to versions of a crate, like
+/// `serde@1.0.9::de::Deserialize` or `tokio@0.1`.
+struct VersionedQuery {
+    /// The query without the version, like `serde::de::Deserialize`.
+    path: String,
+    krate: Option<String>,
+    version: Option<String>,
 }
 
-/// Returns the module paths which contain all the provided path segments.
-fn get_all_matching_paths(query: String,
-                          module_expansions: &ModuleExpansions)
-                          -> Vec<String> {
-    let query_lower = query.to_lowercase();
-    let path_segments: Vec<String> = query_lower.split("::").map(|s| s.to_string()).collect();
+impl VersionedQuery {
+    fn parse(query: &str) -> Self {
+        let mut segments: Vec<String> = query.split("::").map(|s| s.to_string()).collect();
 
-    let mut result = Vec::new();
+        let (krate, version) = match segments[0].find('@') {
+            Some(pos) => {
+                let version = segments[0][pos + 1..].to_string();
+                segments[0].truncate(pos);
+                (Some(segments[0].to_lowercase()), Some(version))
+            },
+            None => (None, None),
+        };
 
-    for segment in path_segments.into_iter() {
-        if let Some(matches) = module_expansions.get(&segment) {
-            if result.is_empty() {
-                result = matches.iter().cloned().collect();
-            } else {
-                result = intersect(result, &matches)
-            }
+        VersionedQuery {
+            path: segments.join("::"),
+            krate: krate,
+            version: version,
         }
     }
+}
 
-    result.retain(|res| res.to_lowercase().contains(&query_lower));
+/// Returns the crate versions matching `spec`, newest first. The spec is either an exact
+/// version in the store or a Cargo-style version requirement like `0.1`.
+fn matching_versions<'a>(versions: &'a CrateVersions, spec: Option<&str>) -> Vec<&'a CrateVersion> {
+    let mut result: Vec<&CrateVersion> = match spec {
+        Some(spec) => {
+            if let Some((version, _)) = versions.iter().find(|&(v, _)| v == spec) {
+                return vec![version];
+            }
 
+            match VersionReq::parse(spec) {
+                Ok(req) => {
+                    versions.keys()
+                        .filter(|v| Version::parse(v).map_or(false, |v| req.matches(&v)))
+                        .collect()
+                },
+                Err(_) => Vec::new(),
+            }
+        },
+        None => versions.keys().collect(),
+    };
+
+    result.sort_by(|a, b| compare_versions(b, a));
     result
 }
 
-/// Returns the strings that exist in both `target` and `other`.
-fn intersect(target: Vec<String>, other: &HashSet<String>) -> Vec<String> {
-    let mut in_common = Vec::new();
-    let mut other_vec: Vec<_> = other.iter().collect();
+/// The crate versions whose archives are in `backend`, found by the names of the archives so they
+/// can be listed even if the store itself can't be read.
+fn archived_crate_versions(backend: &Backend) -> Result<Vec<String>> {
+    let mut crates: Vec<String> = backend.list()?
+        .iter()
+        .filter_map(|name| paths::archive_crate_version(name))
+        .collect();
+    crates.sort();
+    Ok(crates)
+}
+
+/// The problems found by `Store::check_integrity`.
+#[derive(Debug)]
+pub struct IntegrityReport {
+    /// The number of documents checked.
+    pub checked: usize,
+
+    /// Documents in the store without a documentation file.
+    pub missing: Vec<StoreLocation>,
+
+    /// Documents whose file could not be loaded, along with the error.
+    pub corrupt: Vec<(StoreLocation, String)>,
+
+    /// Names of files in the backend which aren't referenced by the store.
+    pub orphaned: Vec<String>,
 
-    for e1 in target.into_iter() {
-        if let Some(pos) = other_vec.iter().position(|e2| e1 == **e2) {
-            in_common.push(e1);
-            other_vec.remove(pos);
+    /// Directories named like the crate directories of old versions of oxidoc. They are only
+    /// matched by name, so they may hold files of something else.
+    pub legacy_dirs: Vec<String>,
+}
+
+impl IntegrityReport {
+    fn new() -> Self {
+        IntegrityReport {
+            checked: 0,
+            missing: Vec::new(),
+            corrupt: Vec::new(),
+            orphaned: Vec::new(),
+            legacy_dirs: Vec::new(),
         }
     }
 
-    in_common
+    /// Whether no problems were found. Legacy directories don't count, since they may not
+    /// belong to oxidoc.
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.corrupt.is_empty() && self.orphaned.is_empty()
+    }
+}
+
+fn latest_version(versions: &CrateVersions) -> Option<&CrateVersion> {
+    versions.keys().max_by(|a, b| compare_versions(a, b))
+}
+
+/// Compares two crate version strings by Semantic Versioning precedence, so `1.0.0-beta.2` is
+/// older than `1.0.0` and `0.300.0` is newer than `0.3.0`. If either version isn't valid
+/// SemVer, both are compared segment by segment, so `1.0` is still newer than `0.1.0`.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    match (Version::parse(a), Version::parse(b)) {
+        // Build metadata doesn't affect precedence, but still needs a stable order.
+        (Ok(va), Ok(vb)) => va.cmp(&vb).then_with(|| a.cmp(b)),
+        _                => compare_version_segments(a, b),
+    }
 }
 
-/// Returns an integer that can be used to compare Semantic Versioning strings.
-fn version_number_hash(version: &str) -> u64 {
-    let slice: Vec<String> = version.split(".").map(|s| s.to_string()).collect();
-    if slice.len() != 3 {
-        return 0;
+/// Fallback comparison for versions which can't be parsed, like ones with components too large
+/// for a `u64`. Numeric segments are compared by value, everything else lexically.
+fn compare_version_segments(a: &str, b: &str) -> Ordering {
+    let separators: &[char] = &['.', '-', '+'];
+    let mut segments_a = a.split(separators);
+    let mut segments_b = b.split(separators);
+
+    loop {
+        let ordering = match (segments_a.next(), segments_b.next()) {
+            (Some(x), Some(y)) => {
+                let is_numeric = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_digit(10));
+                if is_numeric(x) && is_numeric(y) {
+                    let x = x.trim_left_matches('0');
+                    let y = y.trim_left_matches('0');
+                    x.len().cmp(&y.len()).then_with(|| x.cmp(y))
+                } else {
+                    x.cmp(y)
+                }
+            },
+            (Some(_), None) => Ordering::Greater,
+            (None, Some(_)) => Ordering::Less,
+            (None, None)    => return Ordering::Equal,
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
     }
-    let a = slice[0].parse::<u64>().unwrap();
-    let b = slice[1].parse::<u64>().unwrap();
-    let c = slice[2].parse::<u64>().unwrap();
-    (a << 16) + (b << 8) + c
 }
 
 /// A set of documentation for a specific crate version.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Docset {
-    /// Mapping from a crate-local module path string to the corresponding location
-    /// "vec::Vec" => StoreLocation { name: Vec, /* ... */ }
+    /// Mapping from a document key, made of the item's kind and exact crate-local module path,
+    /// to the corresponding location.
+    /// "sdesc-vec::Vec" => StoreLocation { name: Vec, /* ... */ }
     pub documents: HashMap<String, StoreLocation>,
+
+    /// Mapping from a lowercased crate-local module path string to the keys of the documents
+    /// with that path, for case-insensitive searching.
+    /// "vec::vec" => ["sdesc-vec::Vec"]
+    pub paths: HashMap<String, Vec<String>>,
+
+    /// Mapping from the document key of a type to the type it dereferences to.
+    /// "sdesc-string::String" => "str"
+    pub deref_targets: HashMap<String, ModPath>,
 }
 
 impl Docset {
     pub fn new() -> Self {
         Docset {
             documents: HashMap::new(),
+            paths: HashMap::new(),
+            deref_targets: HashMap::new(),
+        }
+    }
+
+    /// Writes the documentation for a crate version into its archive in `backend`, replacing any
+    /// earlier archive, and indexes it.
+    pub fn from_documents(backend: &Arc<Backend>, documents: Vec<Documentation>) -> Result<Self> {
+        let mut docset = Docset::new();
+
+        let crate_info = match documents.first() {
+            Some(doc) => doc.crate_info.clone(),
+            None      => return Ok(docset),
+        };
+
+        let archive_name = paths::crate_archive_name(&crate_info)?;
+        let locations = write_archive(backend, &archive_name, &documents)
+            .chain_err(|| format!("Could not write documentation archive for {}", crate_info))?;
+
+        for (document, location) in documents.iter().zip(locations.into_iter()) {
+            if let Some(target) = document.deref_target() {
+                docset.deref_targets.insert(location.key(), target);
+            }
+            docset.insert(location);
         }
+
+        Ok(docset)
     }
 
-    fn add_doc(&mut self, document: Documentation) -> Result<()> {
-        let relative_path = document.mod_path.tail().to_string();
-        let store_location = document.to_store_location();
-        self.documents.insert(relative_path.to_lowercase(), store_location);
-        document.save()
-            .chain_err(|| format!("Could not add doc {} to docset", document.mod_path))
+    /// Adds the location of a document, replacing one with the same key.
+    pub fn insert(&mut self, location: StoreLocation) {
+        let key = location.key();
+        let relative_path = location.mod_path.tail().to_string().to_lowercase();
+
+        let keys = self.paths.entry(relative_path).or_insert(Vec::new());
+        if !keys.contains(&key) {
+            keys.push(key.clone());
+        }
+        self.documents.insert(key, location);
     }
 
-    pub fn add_docs(&mut self, documents: Vec<Documentation>) -> Result<()> {
-        for doc in documents.into_iter() {
-            self.add_doc(doc)?;
+    fn remove(&mut self, key: &str) {
+        if let Some(location) = self.documents.remove(key) {
+            let relative_path = location.mod_path.tail().to_string().to_lowercase();
+            let is_empty = match self.paths.get_mut(&relative_path) {
+                Some(keys) => {
+                    keys.retain(|k| k != key);
+                    keys.is_empty()
+                },
+                None => false,
+            };
+            if is_empty {
+                self.paths.remove(&relative_path);
+            }
         }
-        Ok(())
+        self.deref_targets.remove(key);
+    }
+
+    /// Returns the number of documents in the docset.
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
     }
+
+    /// Counts the documents of each kind, sorted by kind.
+    pub fn count_by_type(&self) -> Vec<(DocType, usize)> {
+        let mut counts: HashMap<DocType, usize> = HashMap::new();
+        for location in self.documents.values() {
+            *counts.entry(location.doc_type.clone()).or_insert(0) += 1;
+        }
+
+        let mut counts: Vec<(DocType, usize)> = counts.into_iter().collect();
+        counts.sort_by_key(|&(ref doc_type, _)| doc_type.to_string());
+        counts
+    }
+
+    /// Returns the documents directly below the full module path `path`, ignoring case, sorted
+    /// by name.
+    pub fn children(&self, path: &ModPath, include_private: bool) -> Vec<&StoreLocation> {
+        let path = path.to_string().to_lowercase();
+
+        let mut children: Vec<&StoreLocation> = self.documents.values()
+            .filter(|loc| include_private || !loc.is_private)
+            .filter(|loc| loc.mod_path.parent().map_or(false, |p| p.to_string().to_lowercase() == path))
+            .collect();
+
+        sort_by_name(&mut children);
+        children
+    }
+
+    /// Returns everything below the full module path `path`, ignoring case, in depth-first order
+    /// along with the depth of each document.
+    pub fn tree(&self, path: &ModPath, include_private: bool) -> Vec<(usize, &StoreLocation)> {
+        let mut children: HashMap<String, Vec<&StoreLocation>> = HashMap::new();
+        for location in self.documents.values() {
+            if !include_private && location.is_private {
+                continue;
+            }
+            if let Some(parent) = location.mod_path.parent() {
+                children.entry(parent.to_string().to_lowercase())
+                    .or_insert(Vec::new())
+                    .push(location);
+            }
+        }
+        for locations in children.values_mut() {
+            sort_by_name(locations);
+        }
+
+        let mut result = Vec::new();
+        let mut visited = HashSet::new();
+        add_subtree(&children, &path.to_string().to_lowercase(), 0, &mut visited, &mut result);
+        result
+    }
+
+    /// Returns the documents whose crate-local module path matches `path`, ignoring case.
+    pub fn find(&self, path: &str) -> Vec<&StoreLocation> {
+        match self.paths.get(&path.to_lowercase()) {
+            Some(keys) => keys.iter().filter_map(|key| self.documents.get(key)).collect(),
+            None       => Vec::new(),
+        }
+    }
+}
+
+fn sort_by_name(locations: &mut Vec<&StoreLocation>) {
+    locations.sort_by(|a, b| {
+        a.name.to_lowercase().cmp(&b.name.to_lowercase())
+            .then_with(|| a.doc_type.kind_name().cmp(b.doc_type.kind_name()))
+    });
+}
+
+/// Adds the documents below `path` to `result`. Items of different kinds with the same path,
+/// like a module and a function, share their children, which are only added once.
+fn add_subtree<'a>(children: &HashMap<String, Vec<&'a StoreLocation>>,
+                   path: &str,
+                   depth: usize,
+                   visited: &mut HashSet<String>,
+                   result: &mut Vec<(usize, &'a StoreLocation)>) {
+    if !visited.insert(path.to_string()) {
+        return;
+    }
+
+    if let Some(locations) = children.get(path) {
+        for location in locations.iter() {
+            result.push((depth, *location));
+            let child_path = location.mod_path.to_string().to_lowercase();
+            add_subtree(children, &child_path, depth + 1, visited, result);
+        }
+    }
+}
+
+/// Returns the key of a document in a `Docset`. Items are keyed by their kind and exact
+/// crate-local module path, so items which only differ by case or kind don't replace each other.
+pub fn document_key(doc_type: &DocType, relative_path: &str) -> String {
+    format!("{}{}", doc_type.get_file_prefix(), relative_path)
 }
 
 /// Represents the on-disk location of a piece of documentation, with additional metadata on the
@@ -300,34 +1566,84 @@ pub struct StoreLocation {
     pub crate_info: CrateInfo,
     pub mod_path: ModPath,
     pub doc_type: DocType,
+    /// True if the item is private or `#[doc(hidden)]`. These are only indexed when generating
+    /// with `--document-private-items`.
+    pub is_private: bool,
+    /// True for unsafe functions, methods and traits.
+    pub is_unsafe: bool,
+    /// True for `const fn`s.
+    pub is_const_fn: bool,
+    /// True for methods of types and traits, which are otherwise indexed like functions.
+    pub is_method: bool,
+    /// The position of the document in the archive of its crate version.
+    pub offset: u64,
+    pub length: u64,
+    /// The ID of the archive the offsets refer to.
+    pub archive_id: u64,
+    /// The first paragraph of the item's documentation on one line, so lists of results can be
+    /// shown without loading each document.
+    pub summary: String,
+    /// The signature of the item on one line, like `fn len(&self) -> usize`.
+    pub signature: String,
+    /// The backend of the store the documentation was loaded from. `None` stands for the
+    /// writable store.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub backend: Option<Arc<Backend>>,
 }
 
 impl StoreLocation {
     pub fn new(name: String,
                crate_info: CrateInfo,
                mod_path: ModPath,
-               doc_type: DocType) -> Self
+               doc_type: DocType,
+               is_private: bool) -> Self
     {
         StoreLocation {
             name: name,
             crate_info: crate_info,
             mod_path: mod_path,
             doc_type: doc_type,
+            is_private: is_private,
+            is_unsafe: false,
+            is_const_fn: false,
+            is_method: false,
+            offset: 0,
+            length: 0,
+            archive_id: 0,
+            summary: String::new(),
+            signature: String::new(),
+            backend: None,
+        }
+    }
+
+    /// Returns the key of the document in its `Docset`.
+    pub fn key(&self) -> String {
+        document_key(&self.doc_type, &self.mod_path.tail().to_string())
+    }
+
+    /// Returns the summary shortened to at most `max_len` characters.
+    pub fn short_summary(&self, max_len: usize) -> String {
+        if self.summary.chars().count() <= max_len {
+            return self.summary.clone();
         }
+
+        let mut summary: String = self.summary.chars().take(max_len.saturating_sub(3)).collect();
+        summary.push_str("...");
+        summary
     }
 
-    pub fn to_filepath(&self) -> PathBuf {
-        let mut path = paths::crate_doc_path(&self.crate_info).unwrap();
-        let doc_path = self.mod_path.to_filepath();
-        path.push(doc_path);
-        let filename = format!("{}{}.odoc", self.doc_type.get_file_prefix(), self.name);
-        path.push(filename);
-        path
+    /// Returns the name of the archive containing the documentation in its backend.
+    pub fn archive_name(&self) -> Result<String> {
+        paths::crate_archive_name(&self.crate_info)
     }
 }
 
 impl fmt::Display for StoreLocation {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{} ({} {})", self.mod_path, self.crate_info.name, self.crate_info.version)
+        write!(f, "{} ({} {})", self.mod_path, self.crate_info.name, self.crate_info.version)?;
+        if self.is_private {
+            write!(f, " [private]")?;
+        }
+        Ok(())
     }
 }
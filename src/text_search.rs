@@ -0,0 +1,318 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use backend::Backend;
+use conversion::Documentation;
+use document::CrateInfo;
+use store;
+use ::errors::*;
+
+/// Name of the full-text index in a backend, next to the store index.
+pub const TEXT_INDEX_NAME: &str = "text-index";
+
+/// BM25 parameters. `K1` limits how much repeating a term raises the score, `B` how much longer
+/// documents are penalized.
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+/// Words too common to be worth indexing.
+const STOPWORDS: &'static [&'static str] = &[
+    "a", "about", "above", "after", "again", "against", "all", "am", "an", "and", "any", "are",
+    "as", "at", "be", "because", "been", "before", "being", "below", "between", "both", "but",
+    "by", "can", "could", "did", "do", "does", "doing", "down", "during", "each", "few", "for",
+    "from", "further", "had", "has", "have", "having", "he", "her", "here", "hers", "herself",
+    "him", "himself", "his", "how", "i", "if", "in", "into", "is", "it", "its", "itself", "just",
+    "me", "more", "most", "my", "myself", "no", "nor", "not", "now", "of", "off", "on", "once",
+    "only", "or", "other", "our", "ours", "ourselves", "out", "over", "own", "same", "she",
+    "should", "so", "some", "such", "than", "that", "the", "their", "theirs", "them",
+    "themselves", "then", "there", "these", "they", "this", "those", "through", "to", "too",
+    "under", "until", "up", "very", "was", "we", "were", "what", "when", "where", "which",
+    "while", "who", "whom", "why", "will", "with", "would", "you", "your", "yours", "yourself",
+    "yourselves",
+];
+
+/// Splits text into lowercase words, dropping stopwords, and reduces each word to its stem so
+/// that `encodes`, `encoded` and `encoding` are the same term.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .filter(|word| !STOPWORDS.contains(&word.as_str()))
+        .map(|word| stem(&word))
+        .collect()
+}
+
+/// Reduces a lowercase English word to its stem by removing common suffixes. This is a much
+/// simplified Porter stemmer: it only needs to map the forms of a word to the same stem, not to
+/// produce a real word.
+pub fn stem(word: &str) -> String {
+    if word.len() <= 2 || !word.bytes().all(|b| b < 128) {
+        return word.to_string();
+    }
+
+    let mut stem = word.to_string();
+
+    // Plurals: "libraries" => "librari", "addresses" => "address", "bytes" => "byte"
+    if stem.ends_with("sses") {
+        stem.truncate(word.len() - 2);
+    } else if stem.ends_with("ies") {
+        stem.truncate(word.len() - 2);
+    } else if stem.ends_with('s') && !stem.ends_with("ss") && !stem.ends_with("us") &&
+        !stem.ends_with("is") && stem.len() > 3 {
+        stem.pop();
+    }
+
+    // Past tenses and gerunds: "encoded" => "encod", "running" => "run", but not "string"
+    if !stem.ends_with("eed") {
+        for suffix in ["ed", "ing"].iter() {
+            if stem.ends_with(suffix) && contains_vowel(&stem[..stem.len() - suffix.len()]) {
+                let len = stem.len() - suffix.len();
+                stem.truncate(len);
+
+                let bytes = stem.as_bytes().to_vec();
+                let n = bytes.len();
+                if n >= 2 && bytes[n - 1] == bytes[n - 2] && !is_vowel(bytes[n - 1]) &&
+                    !b"lsz".contains(&bytes[n - 1]) {
+                    stem.pop();
+                }
+                break;
+            }
+        }
+    }
+
+    // "library" => "librari", like the plural
+    if stem.ends_with('y') && contains_vowel(&stem[..stem.len() - 1]) {
+        stem.pop();
+        stem.push('i');
+    }
+
+    // "encode" => "encod", like the other forms
+    if stem.ends_with('e') && stem.len() > 2 {
+        stem.pop();
+    }
+
+    stem
+}
+
+fn is_vowel(b: u8) -> bool {
+    b"aeiouy".contains(&b)
+}
+
+fn contains_vowel(s: &str) -> bool {
+    s.bytes().any(|b| b"aeiou".contains(&b))
+}
+
+/// The full-text index of the documentation of one crate version.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct DocsetTextIndex {
+    /// Mapping from a term to the documents containing it, by key in the `Docset`, and the number
+    /// of times it occurs in each.
+    /// "encod" => [("percent_encode", 2), ...]
+    pub postings: HashMap<String, Vec<(String, u32)>>,
+
+    /// Mapping from a document key to the number of terms in the document.
+    pub lengths: HashMap<String, u32>,
+}
+
+impl DocsetTextIndex {
+    /// Indexes the documentation text of the given documents.
+    pub fn from_documents(documents: &[Documentation]) -> Self {
+        let mut index = DocsetTextIndex::default();
+
+        for document in documents.iter() {
+            let terms = tokenize(&document.attrs.doc_strings.join("\n"));
+            if terms.is_empty() {
+                continue;
+            }
+
+            let key = document.to_store_location().key();
+            let mut counts: HashMap<String, u32> = HashMap::new();
+            for term in terms.iter() {
+                *counts.entry(term.clone()).or_insert(0) += 1;
+            }
+
+            for (term, count) in counts {
+                index.postings.entry(term).or_insert(Vec::new()).push((key.clone(), count));
+            }
+            index.lengths.insert(key, terms.len() as u32);
+        }
+
+        index
+    }
+}
+
+/// Full-text indexes of the documentation in a store, organized like the store by crate and
+/// version. It's kept apart from the store index, since it's only needed for `--text` searches.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct TextIndex {
+    /// "serde" => "1.0.0" => DocsetTextIndex { /* ... */ }
+    crates: HashMap<String, HashMap<String, DocsetTextIndex>>,
+}
+
+impl TextIndex {
+    pub fn new() -> Self {
+        TextIndex::default()
+    }
+
+    /// Loads the full-text index kept in `backend`, or returns an empty one if there is none.
+    pub fn load_from(backend: &Arc<Backend>) -> Result<Self> {
+        if !backend.exists(TEXT_INDEX_NAME) {
+            return Ok(TextIndex::new());
+        }
+
+        let data = backend.read(TEXT_INDEX_NAME)?;
+        store::decode_object(&data, &backend.describe(TEXT_INDEX_NAME))
+    }
+
+    pub fn save_to(&self, backend: &Arc<Backend>) -> Result<()> {
+        let data = store::encode_object(self)
+            .chain_err(|| format!("Could not serialize {}", backend.describe(TEXT_INDEX_NAME)))?;
+
+        backend.write(TEXT_INDEX_NAME, data.as_slice())
+    }
+
+    pub fn get(&self, krate_name: &str, version: &str) -> Option<&DocsetTextIndex> {
+        self.crates.get(krate_name).and_then(|versions| versions.get(version))
+    }
+
+    /// Adds the index of a crate version, replacing an earlier one.
+    pub fn insert(&mut self, crate_info: &CrateInfo, index: DocsetTextIndex) {
+        self.crates.entry(crate_info.name.clone())
+            .or_insert(HashMap::new())
+            .insert(crate_info.version.clone(), index);
+    }
+
+    pub fn remove(&mut self, krate_name: &str, version: &str) {
+        let is_empty = match self.crates.get_mut(krate_name) {
+            Some(versions) => {
+                versions.remove(version);
+                versions.is_empty()
+            },
+            None => false,
+        };
+
+        if is_empty {
+            self.crates.remove(krate_name);
+        }
+    }
+
+    /// Removes the index of a single document, like one which could not be loaded anymore.
+    pub fn remove_document(&mut self, krate_name: &str, version: &str, key: &str) {
+        if let Some(index) = self.crates.get_mut(krate_name).and_then(|v| v.get_mut(version)) {
+            index.lengths.remove(key);
+            for postings in index.postings.values_mut() {
+                postings.retain(|&(ref k, _)| k != key);
+            }
+            index.postings.retain(|_, postings| !postings.is_empty());
+        }
+    }
+
+    /// Adds the crate versions of `other` which aren't in this index yet.
+    pub fn merge(&mut self, other: TextIndex) {
+        for (krate_name, versions) in other.crates {
+            let entry = self.crates.entry(krate_name).or_insert(HashMap::new());
+            for (version, index) in versions {
+                entry.entry(version).or_insert(index);
+            }
+        }
+    }
+}
+
+/// Scores documents for a query with BM25 over the given crate version indexes. Returns the
+/// score of each matching document, keyed by the position of its index in `indexes` and its key.
+pub fn score_documents(indexes: &[&DocsetTextIndex], query: &str) -> HashMap<(usize, String), f64> {
+    let mut terms = tokenize(query);
+    terms.sort();
+    terms.dedup();
+
+    let mut scores = HashMap::new();
+
+    let document_count: usize = indexes.iter().map(|index| index.lengths.len()).sum();
+    if document_count == 0 {
+        return scores;
+    }
+
+    let total_length: u64 = indexes.iter()
+        .flat_map(|index| index.lengths.values())
+        .map(|&length| length as u64)
+        .sum();
+    let average_length = total_length as f64 / document_count as f64;
+
+    for term in terms.iter() {
+        let frequency: usize = indexes.iter()
+            .map(|index| index.postings.get(term).map_or(0, |p| p.len()))
+            .sum();
+        if frequency == 0 {
+            continue;
+        }
+
+        let n = document_count as f64;
+        let df = frequency as f64;
+        let idf = (1.0 + (n - df + 0.5) / (df + 0.5)).ln();
+
+        for (i, index) in indexes.iter().enumerate() {
+            let postings = match index.postings.get(term) {
+                Some(postings) => postings,
+                None           => continue,
+            };
+
+            for &(ref key, count) in postings.iter() {
+                let length = index.lengths.get(key).cloned().unwrap_or(0) as f64;
+                let tf = count as f64;
+                let score = idf * tf * (K1 + 1.0) /
+                    (tf + K1 * (1.0 - B + B * length / average_length));
+
+                *scores.entry((i, key.clone())).or_insert(0.0) += score;
+            }
+        }
+    }
+
+    scores
+}
+
+/// The number of words shown before the first match in a snippet, if possible.
+const SNIPPET_CONTEXT: usize = 2;
+
+/// Returns the passage of about `width` words of `text` with the most words matching the query,
+/// with the matching words passed through `highlight`.
+pub fn snippet<F>(text: &str, query: &str, width: usize, highlight: F) -> String
+    where F: Fn(&str) -> String
+{
+    let terms: HashSet<String> = tokenize(query).into_iter().collect();
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let matches: Vec<bool> = words.iter()
+        .map(|word| tokenize(word).iter().any(|term| terms.contains(term)))
+        .collect();
+
+    let width = width.max(1);
+    let mut best_start = 0;
+    let mut best_count = 0;
+    for start in 0..words.len().saturating_sub(width) + 1 {
+        let end = (start + width).min(words.len());
+        let count = matches[start..end].iter().filter(|&&m| m).count();
+        if count > best_count {
+            best_start = start;
+            best_count = count;
+        }
+    }
+
+    // Start shortly before the first match, to show some context but as many matches as possible.
+    if let Some(first) = (best_start..words.len()).find(|&i| matches[i]) {
+        best_start = best_start.max(first.saturating_sub(SNIPPET_CONTEXT))
+            .min(words.len().saturating_sub(width));
+    }
+
+    let end = (best_start + width).min(words.len());
+    let mut parts: Vec<String> = (best_start..end).map(|i| {
+        if matches[i] { highlight(words[i]) } else { words[i].to_string() }
+    }).collect();
+
+    if best_start > 0 {
+        parts.insert(0, "...".to_string());
+    }
+    if end < words.len() {
+        parts.push("...".to_string());
+    }
+
+    parts.join(" ")
+}
@@ -2,19 +2,26 @@ use std::sync::Mutex;
 
 use conversion::Documentation;
 use cursive::{self, Cursive};
-use cursive::align::HAlign;
 use cursive::traits::*;
 use cursive::views::{EditView, LinearLayout, Dialog, SelectView, TextView};
 use driver::Driver;
-use markup::{MarkupDoc, Format};
+use markup::Format;
 use store::{Store, StoreLocation};
 use errors::*;
 
+/// The width of the list of search results.
+const RESULT_WIDTH: usize = 80;
+
+/// The number of search results shown. Short queries match many items fuzzily.
+const MAX_RESULTS: usize = 200;
+
 lazy_static! {
-    static ref STORE: Mutex<Store> = Mutex::new(Store::load());
+    static ref STORE: Mutex<Store> = Mutex::new(Store::new());
 }
 
 pub fn run() -> Result<()> {
+    *STORE.lock().unwrap() = Store::load_layered()?;
+
     let mut siv = Cursive::new();
 
     show_search_screen(&mut siv);
@@ -35,22 +42,36 @@ fn update_search_results(siv: &mut Cursive, query: &str, _length: usize) {
         .unwrap()
         .lookup_name(query)
         .into_iter()
+        .take(MAX_RESULTS)
         .cloned()
         .collect();
 
     for location in matches {
-        results.add_item(location.mod_path.to_string(), location);
+        results.add_item(result_label(&location), location);
+    }
+}
+
+/// Shows the path of a result along with its summary from the store index, so the documentation
+/// doesn't have to be loaded for every result.
+fn result_label(location: &StoreLocation) -> String {
+    let path = location.mod_path.to_string();
+    let width = RESULT_WIDTH.saturating_sub(path.chars().count() + 3);
+
+    if location.summary.is_empty() || width < 10 {
+        path
+    } else {
+        format!("{} - {}", path, location.short_summary(width))
     }
 }
 
 fn show_search_screen(siv: &mut Cursive) {
-    let mut result_list: SelectView<StoreLocation> = SelectView::new().h_align(HAlign::Center);
+    let mut result_list: SelectView<StoreLocation> = SelectView::new();
     let mut search_box = EditView::new();
 
     // Sets the callback for when "Enter" is pressed.
     result_list.set_on_submit(show_next_window);
 
-    // Not fast enough for realtime fuzzy matching...
+    // Results are matched fuzzily and updated while typing.
     search_box.set_on_edit(update_search_results);
     // search_box.set_on_submit(update_search_results);
 
@@ -59,28 +80,76 @@ fn show_search_screen(siv: &mut Cursive) {
         .child(result_list.with_id("results"));
 
     // Let's add a BoxView to keep the list at a reasonable size - it can scroll anyway.
-    siv.add_layer(Dialog::around(layout.fixed_size((80, 40))).title(
+    siv.add_layer(Dialog::around(layout.fixed_size((RESULT_WIDTH, 40))).title(
         "Documentation search",
     ));
 
     update_search_results(siv, "", 0);
 }
 
-fn show_next_window(siv: &mut Cursive, location: &StoreLocation) {
-    let doc_markup = get_markup(location);
+/// Loads the documentation at `location`. If it was regenerated since the store was loaded, the
+/// store is loaded again and the document is looked up in the new one.
+fn load_doc(location: &StoreLocation) -> Result<Documentation> {
+    match Driver::get_doc(location) {
+        Err(Error(ErrorKind::ArchiveChanged(..), _)) => {
+            let store = Store::load_layered()?;
+            let result = match store.relocate(location) {
+                Some(location) => Driver::get_doc(location),
+                None => Err(format!("{} was removed from the documentation store", location).into()),
+            };
+            *STORE.lock().unwrap() = store;
+            result
+        },
+        result => result,
+    }
+}
 
-    show_doc(siv, doc_markup)
+fn show_next_window(siv: &mut Cursive, location: &StoreLocation) {
+    let mut doc: Documentation = match load_doc(location) {
+        Ok(doc) => doc,
+        Err(e) => {
+            let message = format!("Could not load documentation for {}: {}\n\n\
+                                   Run oxidoc doctor to check the documentation store.",
+                                  location, e);
+            siv.add_layer(Dialog::info(message));
+            return;
+        }
+    };
+    Driver::add_deref_methods(&STORE.lock().unwrap(), &mut doc);
+
+    show_doc(siv, &doc)
 }
 
-fn get_markup(location: &StoreLocation) -> MarkupDoc {
-    let result: Documentation = Driver::get_doc(location).unwrap();
-    result.format()
+/// Finds the documentation for the types in the signature of `doc`, so they can be navigated to.
+fn linked_types(doc: &Documentation) -> Vec<StoreLocation> {
+    let store = STORE.lock().unwrap();
+
+    doc.referenced_types()
+        .iter()
+        .filter_map(|path| store.lookup_path(path))
+        .cloned()
+        .collect()
 }
 
-fn show_doc(siv: &mut Cursive, doc: MarkupDoc) {
-    let text = format!("{}", doc);
+fn show_doc(siv: &mut Cursive, doc: &Documentation) {
+    let text = format!("{}", doc.format());
+    let mut layout = LinearLayout::new(cursive::direction::Orientation::Vertical)
+        .child(TextView::new(text));
+
+    let linked = linked_types(doc);
+    if !linked.is_empty() {
+        let mut types: SelectView<StoreLocation> = SelectView::new();
+        for location in linked {
+            types.add_item(result_label(&location), location);
+        }
+        types.set_on_submit(show_next_window);
+
+        layout = layout
+            .child(TextView::new("Types in signature:"))
+            .child(types);
+    }
 
-    siv.add_layer(Dialog::around(TextView::new(text)).button(
+    siv.add_layer(Dialog::around(layout).button(
         "Back",
         |s| s.pop_layer(),
     ));
@@ -0,0 +1,634 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use backend::Backend;
+use conversion::{Documentation, DocInnerData, FnDecl, TraitItemKind};
+use document::CrateInfo;
+use generation::ast_ty_wrappers::{FnKind, Ty, TyKind, TyPath};
+use store;
+use ::errors::*;
+
+/// Name of the signature index in a backend, next to the store index.
+pub const SIGNATURE_INDEX_NAME: &str = "signature-index";
+
+/// Costs of the differences allowed between a query and a signature. A lower total cost is a
+/// closer match.
+const GENERIC_COST: u32 = 1;
+const REFERENCE_COST: u32 = 1;
+const MISSING_ARGS_COST: u32 = 1;
+const EXTRA_INPUT_COST: u32 = 2;
+
+/// Functions with more arguments than this aren't matched, since trying every order of their
+/// arguments would take too long.
+const MAX_INPUTS: usize = 8;
+
+/// A type in a function signature, reduced to what matters for searching by signature: paths
+/// are shortened to their last segment and lifetimes, mutability and bounds are left out.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SigType {
+    /// A named type and its type arguments, like `Vec<T>`. Slices, arrays, tuples, pointers and
+    /// function pointers are named `[]`, `()`, `*` and `fn`.
+    Named(String, Vec<SigType>),
+    /// `&T` or `&mut T`
+    Ref(Box<SigType>),
+    /// A generic type parameter like `T`.
+    Generic(String),
+    /// `_`, which matches any type.
+    Any,
+}
+
+impl SigType {
+    fn named(name: &str) -> Self {
+        SigType::Named(name.to_string(), Vec::new())
+    }
+
+    fn unit() -> Self {
+        SigType::named("()")
+    }
+
+    fn from_ty(ty: &Ty, self_ty: &SigType) -> Self {
+        match ty.kind {
+            TyKind::Path(ref path) => SigType::from_path(path, self_ty),
+            TyKind::Ref(_, _, ref ty) => SigType::Ref(Box::new(SigType::from_ty(ty, self_ty))),
+            TyKind::Ptr(_, ref ty) => SigType::Named("*".to_string(),
+                                                     vec![SigType::from_ty(ty, self_ty)]),
+            TyKind::Slice(ref ty) |
+            TyKind::Array(ref ty, _) => SigType::Named("[]".to_string(),
+                                                       vec![SigType::from_ty(ty, self_ty)]),
+            TyKind::Tuple(ref tys) => SigType::Named("()".to_string(),
+                                                     tys.iter()
+                                                         .map(|ty| SigType::from_ty(ty, self_ty))
+                                                         .collect()),
+            TyKind::BareFn(ref args, ref output) => {
+                let mut tys: Vec<SigType> = args.iter()
+                    .map(|ty| SigType::from_ty(ty, self_ty))
+                    .collect();
+                tys.push(output.as_ref()
+                         .map(|ty| SigType::from_ty(ty, self_ty))
+                         .unwrap_or(SigType::unit()));
+                SigType::Named("fn".to_string(), tys)
+            },
+            // `impl Iterator<Item = T> + Send` is searched for as `Iterator<T>`.
+            TyKind::TraitObject(ref bounds) |
+            TyKind::ImplTrait(ref bounds) => match bounds.first() {
+                Some(path) => SigType::from_path(path, self_ty),
+                None       => SigType::Any,
+            },
+            TyKind::Never => SigType::named("!"),
+            TyKind::Infer => SigType::Any,
+            TyKind::ImplicitSelf => self_ty.clone(),
+            TyKind::Other => SigType::named("?"),
+        }
+    }
+
+    fn from_path(path: &TyPath, self_ty: &SigType) -> Self {
+        let name = match path.path.name() {
+            Some(segment) => segment.identifier,
+            None          => return SigType::Any,
+        };
+
+        if name == "Self" {
+            return self_ty.clone();
+        }
+
+        let args: Vec<SigType> = path.args.iter()
+            .chain(path.bindings.iter().map(|&(_, ref ty)| ty))
+            .map(|ty| SigType::from_ty(ty, self_ty))
+            .collect();
+
+        if args.is_empty() && path.path.0.len() == 1 && path.resolved.is_none() &&
+            is_generic_name(&name) {
+            SigType::Generic(name)
+        } else {
+            SigType::Named(name, args)
+        }
+    }
+}
+
+/// Whether a type name looks like a generic parameter, like `T`, `K` or `T2`. The generics of
+/// functions aren't documented yet, so their names have to be guessed.
+fn is_generic_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_uppercase() => (),
+        _ => return false,
+    }
+    name.chars().count() <= 2 && chars.all(|c| c.is_uppercase() || c.is_digit(10))
+}
+
+/// The normalized signature of a function or method, as kept in the store index.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FnSignature {
+    pub inputs: Vec<SigType>,
+    /// The return type, `()` if there is none.
+    pub output: SigType,
+}
+
+impl FnSignature {
+    /// Normalizes the signature of a function or method. `self` arguments and `Self` are
+    /// replaced by the implementing type, or by a generic parameter in traits.
+    pub fn from_decl(decl: &FnDecl, self_ty: &SigType) -> Self {
+        FnSignature {
+            inputs: decl.inputs.iter().map(|arg| SigType::from_ty(&arg.ty, self_ty)).collect(),
+            output: decl.output.as_ref()
+                .map(|ty| SigType::from_ty(ty, self_ty))
+                .unwrap_or(SigType::unit()),
+        }
+    }
+
+    /// Returns the normalized signature of the documented item, if it is a function or method.
+    pub fn from_documentation(doc: &Documentation) -> Option<Self> {
+        let parent_ty = || {
+            doc.mod_path.parent()
+                .and_then(|parent| parent.name())
+                .map(|segment| SigType::named(&segment.identifier))
+                .unwrap_or(SigType::Any)
+        };
+
+        match doc.inner_data {
+            DocInnerData::FnDoc(ref func) => {
+                let self_ty = match func.kind {
+                    FnKind::ItemFn => SigType::Any,
+                    FnKind::MethodFromImpl |
+                    FnKind::MethodFromTrait => parent_ty(),
+                };
+                Some(FnSignature::from_decl(&func.decl, &self_ty))
+            },
+            DocInnerData::TraitItemDoc(ref item) => match item.node {
+                // Any type implementing the trait could be `Self`.
+                TraitItemKind::Method(ref sig, _) => {
+                    Some(FnSignature::from_decl(&sig.decl, &SigType::Generic("Self".to_string())))
+                },
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// The signatures of the functions and methods of one crate version.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct DocsetSignatures {
+    /// Mapping from the document key of a function or method to its normalized signature.
+    /// "fdesc-vec::Vec::len" => FnSignature { inputs: [Ref(Named("Vec", []))], output: Named("usize", []) }
+    pub signatures: HashMap<String, FnSignature>,
+}
+
+impl DocsetSignatures {
+    /// Collects the signatures of the functions and methods among the given documents.
+    pub fn from_documents(documents: &[Documentation]) -> Self {
+        let mut signatures = HashMap::new();
+        for document in documents.iter() {
+            if let Some(signature) = FnSignature::from_documentation(document) {
+                signatures.insert(document.to_store_location().key(), signature);
+            }
+        }
+
+        DocsetSignatures {
+            signatures: signatures,
+        }
+    }
+}
+
+/// Signatures of the functions and methods in a store, organized like the store by crate and
+/// version. Like the full-text index, it's kept apart from the store index, so that other
+/// queries don't have to load it.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct SignatureIndex {
+    /// "serde" => "1.0.0" => DocsetSignatures { /* ... */ }
+    crates: HashMap<String, HashMap<String, DocsetSignatures>>,
+}
+
+impl SignatureIndex {
+    pub fn new() -> Self {
+        SignatureIndex::default()
+    }
+
+    /// Loads the signature index kept in `backend`, or returns an empty one if there is none.
+    pub fn load_from(backend: &Arc<Backend>) -> Result<Self> {
+        if !backend.exists(SIGNATURE_INDEX_NAME) {
+            return Ok(SignatureIndex::new());
+        }
+
+        let data = backend.read(SIGNATURE_INDEX_NAME)?;
+        store::decode_object(&data, &backend.describe(SIGNATURE_INDEX_NAME))
+    }
+
+    pub fn save_to(&self, backend: &Arc<Backend>) -> Result<()> {
+        let data = store::encode_object(self)
+            .chain_err(|| format!("Could not serialize {}", backend.describe(SIGNATURE_INDEX_NAME)))?;
+
+        backend.write(SIGNATURE_INDEX_NAME, data.as_slice())
+    }
+
+    pub fn get(&self, krate_name: &str, version: &str) -> Option<&DocsetSignatures> {
+        self.crates.get(krate_name).and_then(|versions| versions.get(version))
+    }
+
+    /// Adds the signatures of a crate version, replacing earlier ones.
+    pub fn insert(&mut self, crate_info: &CrateInfo, signatures: DocsetSignatures) {
+        self.crates.entry(crate_info.name.clone())
+            .or_insert(HashMap::new())
+            .insert(crate_info.version.clone(), signatures);
+    }
+
+    pub fn remove(&mut self, krate_name: &str, version: &str) {
+        let is_empty = match self.crates.get_mut(krate_name) {
+            Some(versions) => {
+                versions.remove(version);
+                versions.is_empty()
+            },
+            None => false,
+        };
+
+        if is_empty {
+            self.crates.remove(krate_name);
+        }
+    }
+
+    /// Removes the signature of a single document.
+    pub fn remove_document(&mut self, krate_name: &str, version: &str, key: &str) {
+        if let Some(docset) = self.crates.get_mut(krate_name).and_then(|v| v.get_mut(version)) {
+            docset.signatures.remove(key);
+        }
+    }
+
+    /// Adds the crate versions of `other` which aren't in this index yet.
+    pub fn merge(&mut self, other: SignatureIndex) {
+        for (krate_name, versions) in other.crates {
+            let entry = self.crates.entry(krate_name).or_insert(HashMap::new());
+            for (version, signatures) in versions {
+                entry.entry(version).or_insert(signatures);
+            }
+        }
+    }
+}
+
+/// A search for functions by their signature, like `&str -> Result<u32, _>`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignatureQuery {
+    /// The argument types to look for, in any order. Functions may take more arguments.
+    pub inputs: Vec<SigType>,
+    /// The return type to look for, or `None` if any return type will do.
+    pub output: Option<SigType>,
+}
+
+impl SignatureQuery {
+    /// Parses a query. Arguments are separated by commas or arrows, and the type after the last
+    /// arrow is the return type: `Vec<T>, usize -> Option<T>` and `Vec<T> -> usize -> Option<T>`
+    /// are the same query. A query without an arrow only gives argument types, and one starting
+    /// with an arrow only the return type.
+    ///
+    /// Single uppercase letters like `T` are generic parameters, which stand for any type, but
+    /// the same one everywhere in the query. `_` stands for any type.
+    pub fn parse(query: &str) -> Result<Self> {
+        let tokens = tokenize(query)?;
+        let mut parser = Parser { tokens: tokens, pos: 0 };
+
+        let mut parts: Vec<Vec<SigType>> = vec![parser.parse_list()?];
+        while parser.eat("->") {
+            parts.push(parser.parse_list()?);
+        }
+        if let Some(token) = parser.peek() {
+            bail!("Unexpected \"{}\" in type signature \"{}\"", token, query);
+        }
+
+        let output = if parts.len() > 1 {
+            let mut last = parts.pop().unwrap();
+            if last.len() != 1 {
+                bail!("Expected a single return type in type signature \"{}\"", query);
+            }
+            last.pop()
+        } else {
+            None
+        };
+
+        let inputs: Vec<SigType> = parts.into_iter().flat_map(|part| part).collect();
+        if inputs.is_empty() && output.is_none() {
+            bail!("Empty type signature \"{}\"", query);
+        }
+
+        Ok(SignatureQuery {
+            inputs: inputs,
+            output: output,
+        })
+    }
+
+    /// Returns the cost of matching the signature to this query, or `None` if it doesn't match.
+    /// Each query argument has to match a different argument of the signature, in any order.
+    pub fn match_cost(&self, signature: &FnSignature) -> Option<u32> {
+        if self.inputs.len() > signature.inputs.len() || signature.inputs.len() > MAX_INPUTS {
+            return None;
+        }
+
+        let extra_inputs = (signature.inputs.len() - self.inputs.len()) as u32;
+        let mut used = vec![false; signature.inputs.len()];
+        self.match_inputs(signature, 0, &mut used, &Bindings::new())
+            .map(|cost| cost + extra_inputs * EXTRA_INPUT_COST)
+    }
+
+    fn match_inputs(&self,
+                    signature: &FnSignature,
+                    i: usize,
+                    used: &mut [bool],
+                    bindings: &Bindings) -> Option<u32> {
+        if i == self.inputs.len() {
+            return match self.output {
+                Some(ref output) => unify(output, &signature.output, &mut bindings.clone()),
+                None             => Some(0),
+            };
+        }
+
+        let mut best = None;
+        for j in 0..signature.inputs.len() {
+            if used[j] {
+                continue;
+            }
+
+            let mut next = bindings.clone();
+            let cost = match unify(&self.inputs[i], &signature.inputs[j], &mut next) {
+                Some(cost) => cost,
+                None       => continue,
+            };
+
+            used[j] = true;
+            if let Some(rest) = self.match_inputs(signature, i + 1, used, &next) {
+                if best.map_or(true, |b| cost + rest < b) {
+                    best = Some(cost + rest);
+                }
+            }
+            used[j] = false;
+        }
+        best
+    }
+}
+
+/// The types the generic parameters of the query and of the signature stand for so far, so
+/// each one stands for the same type everywhere.
+#[derive(Clone, Debug)]
+struct Bindings {
+    query: HashMap<String, SigType>,
+    signature: HashMap<String, SigType>,
+}
+
+impl Bindings {
+    fn new() -> Self {
+        Bindings {
+            query: HashMap::new(),
+            signature: HashMap::new(),
+        }
+    }
+}
+
+/// Returns the cost of matching the query type to the signature type, binding generic
+/// parameters on either side, or `None` if they can't match.
+fn unify(query: &SigType, ty: &SigType, bindings: &mut Bindings) -> Option<u32> {
+    match (query, ty) {
+        (&SigType::Any, _) | (_, &SigType::Any) => Some(0),
+
+        (&SigType::Generic(ref name), _) => {
+            if let Some(bound) = bindings.query.get(name) {
+                return if bound == ty { Some(0) } else { None };
+            }
+            bindings.query.insert(name.clone(), ty.clone());
+            match *ty {
+                SigType::Generic(_) => Some(0),
+                _                   => Some(GENERIC_COST),
+            }
+        },
+
+        (_, &SigType::Generic(ref name)) => {
+            if let Some(bound) = bindings.signature.get(name) {
+                return if bound == query { Some(0) } else { None };
+            }
+            bindings.signature.insert(name.clone(), query.clone());
+            Some(GENERIC_COST)
+        },
+
+        (&SigType::Ref(ref a), &SigType::Ref(ref b)) => unify(a, b, bindings),
+        (&SigType::Ref(ref a), b) => unify(a, b, bindings).map(|cost| cost + REFERENCE_COST),
+        (a, &SigType::Ref(ref b)) => unify(a, b, bindings).map(|cost| cost + REFERENCE_COST),
+
+        (&SigType::Named(ref name_a, ref args_a), &SigType::Named(ref name_b, ref args_b)) => {
+            if !names_match(name_a, name_b) {
+                return None;
+            }
+
+            // `Vec` in a query matches `Vec<T>`, and a method's `self` matches `Vec<T>`.
+            if args_a.is_empty() || args_b.is_empty() {
+                return Some(if args_a.len() == args_b.len() { 0 } else { MISSING_ARGS_COST });
+            }
+            if args_a.len() != args_b.len() {
+                return None;
+            }
+
+            let mut cost = 0;
+            for (a, b) in args_a.iter().zip(args_b.iter()) {
+                match unify(a, b, bindings) {
+                    Some(c) => cost += c,
+                    None    => return None,
+                }
+            }
+            Some(cost)
+        },
+    }
+}
+
+fn names_match(query: &str, name: &str) -> bool {
+    query == name || query.to_lowercase() == name.to_lowercase()
+}
+
+fn tokenize(query: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+
+        if c == '-' {
+            if chars.peek() == Some(&'>') {
+                chars.next();
+                tokens.push("->".to_string());
+                continue;
+            }
+            bail!("Unexpected \"-\" in type signature \"{}\"", query);
+        }
+
+        if c.is_alphanumeric() || c == '_' || c == '\'' || c == ':' {
+            let mut token = c.to_string();
+            while let Some(&next) = chars.peek() {
+                if !(next.is_alphanumeric() || next == '_' || next == ':') {
+                    break;
+                }
+                token.push(next);
+                chars.next();
+            }
+            tokens.push(token);
+            continue;
+        }
+
+        if "<>,&*[]();!=".contains(c) {
+            tokens.push(c.to_string());
+            continue;
+        }
+
+        bail!("Unexpected \"{}\" in type signature \"{}\"", c, query);
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|t| t.as_str())
+    }
+
+    fn next(&mut self) -> Option<String> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<()> {
+        match self.next() {
+            Some(ref t) if t == token => Ok(()),
+            Some(t) => bail!("Expected \"{}\" but found \"{}\" in type signature", token, t),
+            None    => bail!("Expected \"{}\" at the end of type signature", token),
+        }
+    }
+
+    /// Parses comma-separated types up to the next arrow or closing bracket.
+    fn parse_list(&mut self) -> Result<Vec<SigType>> {
+        let mut tys = Vec::new();
+        loop {
+            match self.peek() {
+                None | Some("->") | Some(">") | Some(")") | Some("]") => break,
+                _ => (),
+            }
+
+            if self.peek().map_or(false, |t| t.starts_with('\'')) {
+                // Lifetime arguments don't matter.
+                self.next();
+            } else {
+                tys.push(self.parse_type()?);
+            }
+
+            if !self.eat(",") {
+                break;
+            }
+        }
+        Ok(tys)
+    }
+
+    fn parse_type(&mut self) -> Result<SigType> {
+        let token = match self.next() {
+            Some(token) => token,
+            None        => bail!("Expected a type at the end of type signature"),
+        };
+
+        match token.as_str() {
+            "&" => {
+                if self.peek().map_or(false, |t| t.starts_with('\'')) {
+                    self.next();
+                }
+                self.eat("mut");
+                Ok(SigType::Ref(Box::new(self.parse_type()?)))
+            },
+            "*" => {
+                if !self.eat("const") {
+                    self.eat("mut");
+                }
+                Ok(SigType::Named("*".to_string(), vec![self.parse_type()?]))
+            },
+            "[" => {
+                let ty = self.parse_type()?;
+                if self.eat(";") {
+                    // Skip the array length.
+                    self.next();
+                }
+                self.expect("]")?;
+                Ok(SigType::Named("[]".to_string(), vec![ty]))
+            },
+            "(" => {
+                let mut tys = self.parse_list()?;
+                let trailing_comma = self.tokens.get(self.pos.wrapping_sub(1))
+                    .map_or(false, |t| t == ",");
+                self.expect(")")?;
+                if tys.len() == 1 && !trailing_comma {
+                    Ok(tys.pop().unwrap())
+                } else {
+                    Ok(SigType::Named("()".to_string(), tys))
+                }
+            },
+            "!" => Ok(SigType::named("!")),
+            "_" => Ok(SigType::Any),
+            "fn" => {
+                self.expect("(")?;
+                let mut tys = self.parse_list()?;
+                self.expect(")")?;
+                tys.push(if self.eat("->") { self.parse_type()? } else { SigType::unit() });
+                Ok(SigType::Named("fn".to_string(), tys))
+            },
+            "impl" | "dyn" => self.parse_type(),
+            "mut" | "const" => self.parse_type(),
+            _ if token.starts_with(|c: char| c.is_alphabetic() || c == ':') => {
+                self.parse_path(token)
+            },
+            _ => bail!("Unexpected \"{}\" in type signature", token),
+        }
+    }
+
+    fn parse_path(&mut self, path: String) -> Result<SigType> {
+        let name = match path.split("::").filter(|s| !s.is_empty()).last() {
+            Some(name) => name.to_string(),
+            None       => bail!("Expected a type name in type signature"),
+        };
+
+        let mut args = Vec::new();
+        if self.eat("<") {
+            loop {
+                if self.peek() == Some(">") {
+                    break;
+                }
+
+                // Associated type bindings like `Item = T` are matched by their type.
+                if self.tokens.get(self.pos + 1).map_or(false, |t| t == "=") {
+                    self.pos += 2;
+                }
+
+                if self.peek().map_or(false, |t| t.starts_with('\'')) {
+                    self.next();
+                } else {
+                    args.push(self.parse_type()?);
+                }
+
+                if !self.eat(",") {
+                    break;
+                }
+            }
+            self.expect(">")?;
+        }
+
+        if args.is_empty() && !path.contains("::") && is_generic_name(&name) {
+            Ok(SigType::Generic(name))
+        } else {
+            Ok(SigType::Named(name, args))
+        }
+    }
+}
@@ -0,0 +1,20 @@
+use oxidoc::cli;
+
+#[test]
+fn test_subcommand() {
+    let matches = cli::app().get_matches_from_safe(vec!["oxidoc", "list", "serde"]).unwrap();
+    assert_eq!(matches.subcommand_name(), Some("list"));
+    assert_eq!(matches.value_of("query"), None);
+}
+
+#[test]
+fn test_query_named_like_subcommand() {
+    let matches = cli::app().get_matches_from_safe(vec!["oxidoc", "--", "list"]).unwrap();
+    assert_eq!(matches.subcommand_name(), None);
+    assert_eq!(matches.value_of("query"), Some("list"));
+
+    let matches = cli::app().get_matches_from_safe(vec!["oxidoc", "--brief", "--", "tree"]).unwrap();
+    assert_eq!(matches.subcommand_name(), None);
+    assert!(matches.is_present("brief"));
+    assert_eq!(matches.value_of("query"), Some("tree"));
+}
@@ -1 +1,3 @@
 mod test_paths;
+mod test_traits;
+mod test_fns;
@@ -0,0 +1,96 @@
+use ansi_term::Colour;
+
+use oxidoc::conversion::*;
+use oxidoc::document::ModPath;
+use oxidoc::generation::ast_ty_wrappers::{TyKind, Mutability};
+use oxidoc::markup::{Format, FormatOptions};
+
+use util::{find_doc, source_to_docs};
+
+fn check_fn<F: Fn(&Function)>(docs: &Vec<Documentation>, path: &str, check: F) {
+    match find_doc(docs, path).inner_data {
+        FnDoc(ref func) => check(func),
+        _ => panic!("{} is not a function", path),
+    }
+}
+
+#[test]
+fn test_fn_qualifiers() {
+    let docs = source_to_docs(r#"
+pub fn normal() {}
+pub unsafe extern "C" fn foreign() {}
+pub const fn constant() -> u32 { 0 }
+"#);
+
+    check_fn(&docs, "crate::normal", |func| {
+        assert_eq!(func.unsafety, Unsafety::Normal);
+        assert_eq!(func.abi, Abi::Rust);
+    });
+
+    check_fn(&docs, "crate::foreign", |func| {
+        assert_eq!(func.unsafety, Unsafety::Unsafe);
+        assert_eq!(func.abi, Abi::C);
+        assert_eq!(func.abi.to_string(), "\"C\"");
+    });
+
+    check_fn(&docs, "crate::constant", |func| {
+        assert_eq!(func.constness, Constness::Const);
+        assert_eq!(func.asyncness, Asyncness::NotAsync);
+    });
+}
+
+#[test]
+fn test_render_fn_qualifiers() {
+    let docs = source_to_docs(r#"
+pub fn normal() {}
+pub unsafe extern "C" fn foreign() {}
+pub const fn constant() -> u32 { 0 }
+
+pub trait Thing {
+    unsafe fn raw(&self);
+}
+"#);
+    let rendered = |path: &str| find_doc(&docs, path).format().to_string();
+
+    assert!(rendered("crate::normal").contains("pub fn normal"));
+    assert!(rendered("crate::foreign").contains("pub unsafe extern \"C\" fn foreign"));
+    assert!(rendered("crate::constant").contains("pub const fn constant"));
+    assert!(rendered("crate::Thing::raw").contains("unsafe fn raw"));
+
+    // `unsafe` is only highlighted when asked to, like when printing to a terminal.
+    let options = FormatOptions { highlight: true, ..FormatOptions::default() };
+    let highlighted = |path: &str| find_doc(&docs, path).format_with(&options).to_string();
+    let unsafe_keyword = Colour::Red.bold().paint("unsafe").to_string();
+
+    assert!(highlighted("crate::foreign").contains(&format!("pub {} extern \"C\" fn foreign", unsafe_keyword)));
+    assert!(highlighted("crate::Thing::raw").contains(&format!("{} fn raw", unsafe_keyword)));
+    assert!(!rendered("crate::foreign").contains(&unsafe_keyword));
+
+    // Signatures in the store index aren't highlighted.
+    let signature = |path: &str| find_doc(&docs, path).short_signature();
+    assert!(signature("crate::foreign").starts_with("unsafe extern \"C\" fn foreign"));
+    assert!(signature("crate::constant").starts_with("const fn constant"));
+    assert!(signature("crate::Thing::raw").starts_with("unsafe fn raw"));
+}
+
+#[test]
+fn test_fn_decl_types() {
+    let docs = source_to_docs(r#"
+pub struct Thing;
+
+pub fn make(name: &str, count: u32) -> Option<Thing> { None }
+"#);
+
+    check_fn(&docs, "crate::make", |func| {
+        let names: Vec<&str> = func.decl.inputs.iter().map(|arg| arg.name.as_str()).collect();
+        assert_eq!(names, vec!["name", "count"]);
+
+        match func.decl.inputs[0].ty.kind {
+            TyKind::Ref(None, Mutability::Immutable, ref inner) => assert_eq!(inner.name, "str"),
+            ref other => panic!("Expected a reference, got {:?}", other),
+        }
+
+        let output = func.decl.output.as_ref().expect("No return type");
+        assert_eq!(output.resolved_paths(), vec![ModPath::from("crate::Thing".to_string())]);
+    });
+}
@@ -1,7 +1,8 @@
 use oxidoc::conversion::Documentation;
 use oxidoc::document::ModPath;
+use oxidoc::generation::GenerationOptions;
 
-use util::{source_to_docs, print_paths};
+use util::{source_to_docs, source_to_docs_with_options, print_paths};
 
 fn assert_paths_found(converted: &Vec<Documentation>, mut paths: Vec<&str>) {
     let mut converted_strings: Vec<String> = converted
@@ -63,6 +64,70 @@ pub mod test {
     assert_paths_found(&docs, vec!["crate", "crate::test"]);
 }
 
+#[test]
+fn test_document_private_items() {
+    let options = GenerationOptions { document_private_items: true };
+    let docs = source_to_docs_with_options(
+        r#"
+pub(crate) struct A;
+
+#[doc(hidden)]
+pub fn b() {}
+
+fn c() {}
+"#,
+        &options,
+    );
+    assert_paths_found(&docs, vec!["crate", "crate::A", "crate::b", "crate::c"]);
+
+    let private: Vec<String> = docs.iter()
+        .filter(|doc| doc.is_private())
+        .map(|doc| doc.mod_path.to_string())
+        .collect();
+    assert_eq!(private.len(), 3, "{:?}", private);
+}
+
+#[test]
+fn test_document_private_trait_impls() {
+    let options = GenerationOptions { document_private_items: true };
+    let docs = source_to_docs_with_options(
+        r#"
+pub struct Public;
+struct Private;
+
+trait Helper {
+    fn help(&self);
+}
+
+impl Clone for Public {
+    fn clone(&self) -> Self { Public }
+}
+
+impl Helper for Public {
+    fn help(&self) {}
+}
+
+impl Clone for Private {
+    fn clone(&self) -> Self { Private }
+}
+
+impl Private {
+    pub fn method(&self) {}
+}
+"#,
+        &options,
+    );
+
+    let mut private: Vec<String> = docs.iter()
+        .filter(|doc| doc.is_private())
+        .map(|doc| doc.mod_path.to_string())
+        .collect();
+    private.sort();
+    assert_eq!(private, vec!["crate::Helper", "crate::Helper::help", "crate::Private",
+                             "crate::Private::clone", "crate::Private::method",
+                             "crate::Public::help"]);
+}
+
 #[test]
 fn test_one_method() {
     let docs = source_to_docs(
@@ -86,6 +151,86 @@ pub mod test {
     );
 }
 
+#[test]
+fn test_private_impl_items() {
+    let docs = source_to_docs(
+        r#"
+pub struct MyStruct;
+
+trait Helper {
+    fn help(&self);
+}
+
+impl MyStruct {
+    pub fn method(&self) {}
+    fn private_method(&self) {}
+}
+
+impl Clone for MyStruct {
+    fn clone(&self) -> Self { MyStruct }
+}
+
+impl Helper for MyStruct {
+    fn help(&self) {}
+}
+"#,
+    );
+    assert_paths_found(
+        &docs,
+        vec![
+            "crate",
+            "crate::MyStruct",
+            "crate::MyStruct::method",
+            "crate::MyStruct::clone",
+        ],
+    );
+}
+
+#[test]
+fn test_impl_before_private_type() {
+    let src = r#"
+pub mod a {
+    use b::Private;
+
+    impl Clone for Private {
+        fn clone(&self) -> Self { Private }
+    }
+}
+
+mod b {
+    pub struct Private;
+}
+"#;
+    let docs = source_to_docs(src);
+    assert!(!docs.iter().any(|doc| doc.mod_path.to_string().ends_with("::clone")));
+
+    let options = GenerationOptions { document_private_items: true };
+    let docs = source_to_docs_with_options(src, &options);
+    let clone = docs.iter()
+        .find(|doc| doc.mod_path.to_string().ends_with("::clone"))
+        .expect("Private::clone wasn't documented");
+    assert!(clone.is_private());
+}
+
+#[test]
+fn test_primitive_impls() {
+    let docs = source_to_docs(
+        r#"
+impl str {
+    pub fn shout(&self) {}
+}
+
+impl<T> [T] {
+    fn private(&self) {}
+}
+"#,
+    );
+    assert_paths_found(
+        &docs,
+        vec!["crate", "crate::primitive::str", "crate::primitive::str::shout"],
+    );
+}
+
 #[test]
 fn test_nested_modules() {
     let docs = source_to_docs(
@@ -0,0 +1,42 @@
+use oxidoc::conversion::*;
+
+use util::{find_doc, source_to_docs};
+
+fn link_names(doc: &Documentation, doc_type: DocType) -> Vec<String> {
+    doc.links.get(&doc_type)
+        .map(|links| links.iter().map(|l| l.name.clone()).collect())
+        .unwrap_or(Vec::new())
+}
+
+#[test]
+fn test_required_and_provided_methods() {
+    let docs = source_to_docs(r#"
+pub trait Thing {
+    type Assoc: Clone;
+    fn required(&self);
+    fn provided(&self) -> u32 { 42 }
+}
+"#);
+
+    let trait_doc = find_doc(&docs, "crate::Thing");
+    assert_eq!(link_names(trait_doc, DocType::TraitItemMethod), vec!["required"]);
+    assert_eq!(link_names(trait_doc, DocType::ProvidedMethod), vec!["provided"]);
+
+    match find_doc(&docs, "crate::Thing::provided").inner_data {
+        TraitItemDoc(ref item) => {
+            assert!(!item.node.is_required());
+            match item.node {
+                TraitItemKind::Method(_, Some(ref body)) => assert!(body.contains("42")),
+                _ => panic!("Expected a provided method"),
+            }
+        },
+        _ => panic!("Expected a trait item"),
+    }
+
+    match find_doc(&docs, "crate::Thing::Assoc").inner_data {
+        TraitItemDoc(ref item) => {
+            assert_eq!(item.node, TraitItemKind::Type(vec!["Clone".to_string()], None));
+        },
+        _ => panic!("Expected a trait item"),
+    }
+}
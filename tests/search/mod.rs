@@ -1 +1,6 @@
 mod test_lookup;
+mod test_fuzzy;
+mod test_text;
+mod test_signature;
+mod test_ranking;
+mod test_path_index;
@@ -0,0 +1,80 @@
+use oxidoc::search::*;
+use oxidoc::store::Store;
+use util;
+
+fn store_from_source(src: &str) -> Store {
+    util::memory_store_from_source("crate", "1.0.0", src)
+}
+
+fn best_match(store: &Store, query: &str) -> Option<String> {
+    store.lookup_name(query).first().map(|loc| loc.mod_path.to_string())
+}
+
+#[test]
+fn test_segment_match_kinds_are_ranked() {
+    let exact = score_segment("map", "Map").unwrap();
+    let prefix = score_segment("map", "map_keys").unwrap();
+    let substring = score_segment("map", "HashMap").unwrap();
+    let subsequence = score_segment("hshmp", "HashMap").unwrap();
+    let typo = score_segment("hasmhap", "HashMap").unwrap();
+
+    assert!(exact > prefix);
+    assert!(prefix > substring);
+    assert!(substring > subsequence);
+    assert!(subsequence > typo);
+}
+
+#[test]
+fn test_short_queries_are_not_fuzzy() {
+    assert!(score_segment("a", "crate").is_none());
+    assert!(score_segment("a", "abc").is_some());
+    assert!(score_segment("hm", "HashMap").is_none());
+}
+
+#[test]
+fn test_word_boundaries_score_higher() {
+    assert!(score_segment("hsmp", "HashMap").unwrap() > score_segment("hsmp", "Hashmap").unwrap());
+    assert!(score_segment("hsmp", "hash_map").unwrap() > score_segment("hsmp", "hashmap").unwrap());
+}
+
+#[test]
+fn test_path_segments_match_in_order() {
+    let query = query_segments("collections::hashmp");
+    assert!(score_path(&query, &["std", "collections", "HashMap"]).is_some());
+    assert!(score_path(&query, &["std", "HashMap", "collections"]).is_none());
+}
+
+#[test]
+fn test_fuzzy_search() {
+    let store = store_from_source(r#"
+pub struct HashMap;
+pub struct BTreeMap;
+pub mod collections {
+    pub struct HashSet;
+}
+"#);
+
+    assert_eq!(best_match(&store, "hashmp"), Some("crate::HashMap".to_string()));
+    assert_eq!(best_match(&store, "HshMap"), Some("crate::HashMap".to_string()));
+    assert_eq!(best_match(&store, "btreem"), Some("crate::BTreeMap".to_string()));
+    assert_eq!(best_match(&store, "hasmhap"), Some("crate::HashMap".to_string()));
+    assert_eq!(best_match(&store, "collections::hshset"),
+               Some("crate::collections::HashSet".to_string()));
+    assert_eq!(best_match(&store, "xyzzy"), None);
+}
+
+#[test]
+fn test_exact_matches_rank_first() {
+    let store = store_from_source(r#"
+pub struct HashMap;
+pub fn map_keys() {}
+pub struct Map;
+"#);
+
+    let found: Vec<String> = store.lookup_name("map")
+        .into_iter()
+        .map(|loc| loc.mod_path.to_string())
+        .collect();
+
+    assert_eq!(found, vec!["crate::Map", "crate::map_keys", "crate::HashMap"]);
+}
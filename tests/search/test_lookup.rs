@@ -1,23 +1,17 @@
+use std::sync::Arc;
+
+use oxidoc::backend::MemoryBackend;
 use oxidoc::store::Store;
-use oxidoc::generation;
+use oxidoc::generation::GenerationOptions;
+use oxidoc::search::{ItemKind, SearchFilter};
 use util;
 
-fn add_docs(store: &mut Store, krate_name: &str, version: &str, src: &str) {
-    let docs = util::source_to_docs(src);
-    let docset = generation::make_docset(docs).unwrap();
-
-    store.add_docset(util::get_crate_info(krate_name, version), docset);
+fn new_store() -> Store {
+    Store::with_backend(Arc::new(MemoryBackend::new()))
 }
 
 fn store_from_source(src: &str) -> Store {
-    store_from_crate_source("crate", "1.0.0", src)
-}
-
-fn store_from_crate_source(krate_name: &str, version: &str, src: &str) -> Store {
-    let mut store = Store::new();
-    add_docs(&mut store, krate_name, version, src);
-
-    store
+    util::memory_store_from_source("crate", "1.0.0", src)
 }
 
 fn assert_search_query(store: &Store, query: &str, expected_paths: Vec<&str>) {
@@ -34,6 +28,19 @@ fn assert_search_query(store: &Store, query: &str, expected_paths: Vec<&str>) {
             expected_paths.join("\n"));
 }
 
+fn assert_filtered_query(store: &Store, query: &str, filter: &SearchFilter, expected_paths: Vec<&str>) {
+    let mut found_paths: Vec<String> = store.lookup_filtered(query, filter)
+        .into_iter()
+        .map(|r| format!("{}@{}", r.mod_path, r.crate_info.version))
+        .collect();
+    found_paths.sort();
+
+    let mut expected_paths: Vec<String> = expected_paths.iter().map(|p| p.to_string()).collect();
+    expected_paths.sort();
+
+    assert_eq!(found_paths, expected_paths, "Filtered search results for {}", query);
+}
+
 #[test]
 fn test_search_ignores_case() {
     let store = store_from_source("pub struct Test;");
@@ -66,15 +73,281 @@ pub mod nyanko {
 
 #[test]
 fn test_search_for_removed_item() {
-    let mut store = Store::new();
-    add_docs(&mut store, "crate", "0.0.1", r#"
+    let mut store = new_store();
+    util::add_source(&mut store, "crate", "0.0.1", r#"
 pub mod stuff {
     pub fn depreciated() {}
 }
 "#);
-    add_docs(&mut store, "crate", "0.1.0", r#"
+    util::add_source(&mut store, "crate", "0.1.0", r#"
 pub mod stuff {
 }
 "#);
     assert_search_query(&store, "stuff::depreciated", vec!["crate::stuff::depreciated"]);
 }
+
+#[test]
+fn test_search_specific_version() {
+    let mut store = new_store();
+    util::add_source(&mut store, "crate", "0.0.1", r#"
+pub mod stuff {
+    pub fn thing() {}
+}
+"#);
+    util::add_source(&mut store, "crate", "0.1.0", r#"
+pub mod stuff {
+    pub fn thing() {}
+}
+"#);
+    let version_of = |query: &str| -> Vec<String> {
+        store.lookup_name(query).into_iter().map(|r| r.crate_info.version.clone()).collect()
+    };
+
+    assert_eq!(version_of("stuff::thing"), vec!["0.1.0"]);
+    assert_eq!(version_of("crate@0.0.1::stuff::thing"), vec!["0.0.1"]);
+    assert_eq!(version_of("crate@0.1::stuff::thing"), vec!["0.1.0"]);
+    assert_eq!(version_of("crate@0.2::stuff::thing"), Vec::<String>::new());
+    assert_search_query(&store, "crate@0.0.1::stuff::thing", vec!["crate::stuff::thing"]);
+}
+
+#[test]
+fn test_search_after_removing_version() {
+    let mut store = new_store();
+    util::add_source(&mut store, "crate", "0.0.1", r#"
+pub mod stuff {
+    pub fn depreciated() {}
+}
+"#);
+    util::add_source(&mut store, "crate", "0.1.0", r#"
+pub mod stuff {
+    pub fn replacement() {}
+}
+"#);
+    store.retain_newest_versions("crate", 1).unwrap();
+
+    assert_search_query(&store, "depreciated", vec![]);
+    assert_search_query(&store, "stuff", vec!["crate::stuff",
+                                              "crate::stuff::replacement"]);
+
+    store.remove_crate("crate").unwrap();
+    assert_search_query(&store, "stuff", vec![]);
+    assert!(store.remove_crate("crate").is_err());
+}
+
+#[test]
+fn test_search_after_regenerating() {
+    let mut store = new_store();
+    util::add_source(&mut store, "crate", "1.0.0", "pub struct Old;");
+    util::add_source(&mut store, "crate", "1.0.0", "pub struct New;");
+
+    assert_search_query(&store, "old", vec![]);
+    assert_search_query(&store, "new", vec!["crate::New"]);
+}
+
+#[test]
+fn test_private_items_excluded() {
+    let options = GenerationOptions { document_private_items: true };
+    let docs = util::source_to_docs_with_options(r#"
+pub struct Thing;
+struct ThingHelper;
+"#, &options);
+    let mut store = new_store();
+    store.add_documents(util::get_crate_info("crate", "1.0.0"), docs).unwrap();
+
+    assert_search_query(&store, "thing", vec!["crate::Thing"]);
+
+    let mut found: Vec<String> = store.lookup_name_including_private("thinghelper")
+        .into_iter()
+        .map(|r| r.mod_path.to_string())
+        .collect();
+    found.sort();
+    assert_eq!(found, vec!["crate::ThingHelper".to_string()]);
+}
+
+#[test]
+fn test_search_through_deref() {
+    let store = store_from_source(r#"
+use std::ops::Deref;
+
+pub struct Inner;
+
+impl Inner {
+    pub fn inner_method(&self) {}
+}
+
+pub struct Outer(Inner);
+
+impl Deref for Outer {
+    type Target = Inner;
+    fn deref(&self) -> &Inner { &self.0 }
+}
+"#);
+
+    assert_search_query(&store, "Outer::inner_method", vec![]);
+
+    let (target, results) = store.lookup_through_deref("Outer::inner_method")
+        .expect("No results through Deref");
+    let found: Vec<String> = results.into_iter().map(|r| r.mod_path.to_string()).collect();
+
+    assert_eq!(target.to_string(), "crate::Inner");
+    assert_eq!(found, vec!["crate::Inner::inner_method".to_string()]);
+}
+
+#[test]
+fn test_search_through_deref_to_primitives() {
+    let store = store_from_source(r#"
+use std::ops::Deref;
+
+pub struct String(Vec<u8>);
+
+impl Deref for String {
+    type Target = str;
+    fn deref(&self) -> &str { unimplemented!() }
+}
+
+impl str {
+    pub fn trim(&self) -> &str { self }
+}
+
+pub struct Vec<T>(T);
+
+impl<T> Deref for Vec<T> {
+    type Target = [T];
+    fn deref(&self) -> &[T] { unimplemented!() }
+}
+
+impl<T> [T] {
+    pub fn first(&self) -> Option<&T> { None }
+}
+
+pub struct Box<T>(T);
+
+impl<T> Deref for Box<T> {
+    type Target = T;
+    fn deref(&self) -> &T { &self.0 }
+}
+
+pub struct T;
+
+impl T {
+    pub fn method(&self) {}
+}
+"#);
+
+    let (target, results) = store.lookup_through_deref("String::trim")
+        .expect("No results through Deref for String");
+    let found: Vec<String> = results.into_iter().map(|r| r.mod_path.to_string()).collect();
+    assert_eq!(target.to_string(), "str");
+    assert_eq!(found, vec!["crate::primitive::str::trim".to_string()]);
+
+    let (target, results) = store.lookup_through_deref("Vec::first")
+        .expect("No results through Deref for Vec");
+    let found: Vec<String> = results.into_iter().map(|r| r.mod_path.to_string()).collect();
+    assert_eq!(target.to_string(), "slice");
+    assert_eq!(found, vec!["crate::primitive::slice::first".to_string()]);
+
+    // The target of `Box<T>` is a type parameter, not the struct named `T`.
+    assert!(store.lookup_through_deref("Box::method").is_none());
+}
+
+const FILTER_SRC: &str = r#"
+pub struct Error;
+
+impl Error {
+    pub const fn new() -> Error { Error }
+    pub unsafe fn from_raw(code: i32) -> Error { Error }
+}
+
+pub enum ErrorKind {}
+
+pub unsafe trait ErrorSource {}
+
+pub fn error() {}
+
+pub const ERROR_CODE: i32 = 1;
+"#;
+
+#[test]
+fn test_filter_by_kind() {
+    let store = store_from_source(FILTER_SRC);
+
+    let mut filter = SearchFilter::new();
+    filter.kinds = vec![ItemKind::Struct, ItemKind::Enum];
+    assert_filtered_query(&store, "error", &filter,
+                          vec!["crate::Error@1.0.0", "crate::ErrorKind@1.0.0"]);
+
+    filter.kinds = vec![ItemKind::Function];
+    assert_filtered_query(&store, "error", &filter, vec!["crate::error@1.0.0"]);
+
+    filter.kinds = vec![ItemKind::Method];
+    assert_filtered_query(&store, "error", &filter,
+                          vec!["crate::Error::from_raw@1.0.0", "crate::Error::new@1.0.0"]);
+
+    filter.kinds = vec![ItemKind::Const];
+    assert_filtered_query(&store, "error", &filter, vec!["crate::ERROR_CODE@1.0.0"]);
+}
+
+#[test]
+fn test_filter_by_unsafety_and_constness() {
+    let store = store_from_source(FILTER_SRC);
+
+    let mut filter = SearchFilter::new();
+    filter.unsafe_only = true;
+    assert_filtered_query(&store, "error", &filter,
+                          vec!["crate::Error::from_raw@1.0.0", "crate::ErrorSource@1.0.0"]);
+
+    let mut filter = SearchFilter::new();
+    filter.const_fn_only = true;
+    assert_filtered_query(&store, "error", &filter, vec!["crate::Error::new@1.0.0"]);
+}
+
+#[test]
+fn test_filter_by_crate_and_version() {
+    let mut store = new_store();
+    util::add_source(&mut store, "crate", "1.0.0", "pub struct Old; pub struct Both;");
+    util::add_source(&mut store, "crate", "2.0.0", "pub struct New; pub struct Both;");
+
+    let mut filter = SearchFilter::new();
+    filter.krate = Some("crate".to_string());
+    assert_filtered_query(&store, "both", &filter, vec!["crate::Both@2.0.0"]);
+
+    filter.version = Some("1".to_string());
+    assert_filtered_query(&store, "both", &filter, vec!["crate::Both@1.0.0"]);
+    assert_filtered_query(&store, "new", &filter, vec![]);
+
+    filter.krate = Some("other".to_string());
+    filter.version = None;
+    assert_filtered_query(&store, "both", &filter, vec![]);
+}
+
+#[test]
+fn test_filter_query_prefixes() {
+    let store = store_from_source(FILTER_SRC);
+    let filter = SearchFilter::new();
+
+    assert_filtered_query(&store, "fn:error", &filter, vec!["crate::error@1.0.0"]);
+    assert_filtered_query(&store, "unsafe:method:error", &filter,
+                          vec!["crate::Error::from_raw@1.0.0"]);
+    // Path separators aren't prefixes.
+    assert_filtered_query(&store, "error::new", &filter, vec!["crate::Error::new@1.0.0"]);
+
+    let mut filter = SearchFilter::new();
+    assert_eq!(filter.parse_prefixes("struct:Error"), "Error");
+    assert_eq!(filter.kinds, vec![ItemKind::Struct]);
+    assert_eq!(filter.parse_prefixes("std::io::Error"), "std::io::Error");
+    assert_eq!(filter.parse_prefixes("nokind:Error"), "nokind:Error");
+}
+
+#[test]
+fn test_filter_const_and_const_fn_prefixes() {
+    let store = store_from_source(FILTER_SRC);
+    let filter = SearchFilter::new();
+
+    assert_filtered_query(&store, "const:error", &filter, vec!["crate::ERROR_CODE@1.0.0"]);
+    assert_filtered_query(&store, "constfn:error", &filter, vec!["crate::Error::new@1.0.0"]);
+
+    let mut filter = SearchFilter::new();
+    assert_eq!(filter.parse_prefixes("constfn:new"), "new");
+    assert!(filter.const_fn_only);
+    assert!(filter.kinds.is_empty());
+}
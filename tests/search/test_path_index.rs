@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use oxidoc::backend::{Backend, MemoryBackend};
+use oxidoc::path_index::PathIndex;
+use oxidoc::search::query_segments;
+use oxidoc::store::Store;
+use util;
+
+fn index() -> PathIndex {
+    PathIndex::build(vec![
+        "std::collections::HashMap".to_string(),
+        "std::collections::HashSet".to_string(),
+        "std::collections::hash_map::Entry".to_string(),
+        "std::vec::Vec".to_string(),
+        "std::vec".to_string(),
+        "std::vec::Vec".to_string(),
+    ])
+}
+
+#[test]
+fn test_paths_are_deduplicated() {
+    assert_eq!(index().len(), 5);
+}
+
+#[test]
+fn test_paths_with_prefix() {
+    let index = index();
+
+    assert_eq!(index.paths_with_prefix("std::Coll"),
+               ["std::collections::hash_map::entry", "std::collections::hashmap",
+                "std::collections::hashset"]);
+    assert_eq!(index.paths_with_prefix("std::vec"), ["std::vec", "std::vec::vec"]);
+    assert!(index.paths_with_prefix("core").is_empty());
+}
+
+#[test]
+fn test_paths_with_name_prefix() {
+    let index = index();
+
+    assert_eq!(index.paths_with_name_prefix("Hash"),
+               vec!["std::collections::hashmap", "std::collections::hashset"]);
+    assert_eq!(index.paths_with_name_prefix("vec"), vec!["std::vec", "std::vec::vec"]);
+}
+
+#[test]
+fn test_matching_paths() {
+    let index = index();
+
+    let mut found = index.matching_paths(&query_segments("collections::hashmp"));
+    found.sort();
+    assert_eq!(found, vec!["std::collections::hashmap"]);
+
+    let mut found = index.matching_paths(&query_segments("hash_map::entry"));
+    found.sort();
+    assert_eq!(found, vec!["std::collections::hash_map::entry"]);
+
+    assert!(index.matching_paths(&query_segments("vec::hashmap")).is_empty());
+    assert!(index.matching_paths(&query_segments("")).is_empty());
+}
+
+#[test]
+fn test_complete_from_saved_index() {
+    let backend: Arc<Backend> = Arc::new(MemoryBackend::new());
+    let mut store = Store::with_backend(backend.clone());
+    util::add_source(&mut store, "crate", "1.0.0", r#"
+pub mod collections {
+    pub struct HashMap;
+    pub struct HashSet;
+}
+fn hash_private() {}
+"#);
+
+    assert_eq!(store.complete("crate::collections::Hash", 10),
+               vec!["crate::collections::HashMap", "crate::collections::HashSet"]);
+
+    store.save().unwrap();
+    let store = Store::load_from(backend).unwrap();
+
+    assert_eq!(store.complete("hash", 10),
+               vec!["crate::collections::HashMap", "crate::collections::HashSet"]);
+    assert_eq!(store.complete("hash", 1), vec!["crate::collections::HashMap"]);
+    assert_eq!(store.lookup_name("HashSet")[0].mod_path.to_string(), "crate::collections::HashSet");
+}
@@ -0,0 +1,146 @@
+use oxidoc::conversion::DocType;
+use oxidoc::document::ModPath;
+use oxidoc::ranking::*;
+use oxidoc::store::StoreLocation;
+use util;
+
+fn location(path: &str, version: &str, doc_type: DocType) -> StoreLocation {
+    let mod_path = ModPath::from(path.to_string());
+    let krate_name = mod_path.head().unwrap().identifier;
+    let name = mod_path.name().unwrap().identifier;
+
+    StoreLocation::new(name, util::get_crate_info(&krate_name, version), mod_path, doc_type, false)
+}
+
+fn ranked(ranking: &Ranking, query: &str, locations: &[StoreLocation]) -> Vec<String> {
+    let mut results: Vec<&StoreLocation> = locations.iter().collect();
+    ranking.sort(query, &mut results);
+    results.into_iter()
+        .map(|loc| format!("{}@{}", loc.mod_path, loc.crate_info.version))
+        .collect()
+}
+
+#[test]
+fn test_capitalized_query_prefers_types() {
+    let locations = vec![
+        location("other::vec", "1.0.0", DocType::Module),
+        location("std::vec", "1.0.0", DocType::Module),
+        location("std::vec::Vec", "1.0.0", DocType::Struct),
+    ];
+
+    assert_eq!(ranked(&Ranking::new(), "Vec", &locations),
+               vec!["std::vec::Vec@1.0.0", "std::vec@1.0.0", "other::vec@1.0.0"]);
+}
+
+#[test]
+fn test_lowercase_query_prefers_modules_and_functions() {
+    let locations = vec![
+        location("std::vec::Vec", "1.0.0", DocType::Struct),
+        location("std::vec", "1.0.0", DocType::Module),
+    ];
+
+    assert_eq!(ranked(&Ranking::new(), "vec", &locations),
+               vec!["std::vec@1.0.0", "std::vec::Vec@1.0.0"]);
+}
+
+#[test]
+fn test_uppercase_query_prefers_constants() {
+    let locations = vec![
+        location("other::Max", "1.0.0", DocType::Struct),
+        location("other::MAX", "1.0.0", DocType::Const),
+    ];
+
+    assert_eq!(ranked(&Ranking::new(), "MAX", &locations),
+               vec!["other::MAX@1.0.0", "other::Max@1.0.0"]);
+}
+
+#[test]
+fn test_exact_deep_match_ranks_above_shallow_partial_match() {
+    let locations = vec![
+        location("other::Mapping", "1.0.0", DocType::Struct),
+        location("other::a::b::c::Map", "1.0.0", DocType::Struct),
+    ];
+
+    assert_eq!(ranked(&Ranking::new(), "Map", &locations),
+               vec!["other::a::b::c::Map@1.0.0", "other::Mapping@1.0.0"]);
+}
+
+#[test]
+fn test_std_and_dependencies_rank_higher() {
+    let locations = vec![
+        location("other::Error", "1.0.0", DocType::Struct),
+        location("failure::Error", "1.0.0", DocType::Struct),
+        location("std::Error", "1.0.0", DocType::Trait),
+    ];
+
+    assert_eq!(ranked(&Ranking::new(), "Error", &locations),
+               vec!["std::Error@1.0.0", "failure::Error@1.0.0", "other::Error@1.0.0"]);
+
+    let mut ranking = Ranking::new();
+    ranking.dependencies.insert("other".to_string());
+    assert_eq!(ranked(&ranking, "Error", &locations),
+               vec!["std::Error@1.0.0", "other::Error@1.0.0", "failure::Error@1.0.0"]);
+
+    ranking.weights.dependency = ranking.weights.std_crate + 1;
+    assert_eq!(ranked(&ranking, "Error", &locations)[0], "other::Error@1.0.0");
+}
+
+#[test]
+fn test_ties_are_broken_deterministically() {
+    let locations = vec![
+        location("b::Thing", "1.0.0", DocType::Struct),
+        location("a::Thing", "1.0.0", DocType::Struct),
+        location("a::Thing", "1.10.0", DocType::Struct),
+        location("a::Thing", "1.9.0", DocType::Struct),
+    ];
+    let expected = vec!["a::Thing@1.10.0", "a::Thing@1.9.0", "a::Thing@1.0.0", "b::Thing@1.0.0"];
+
+    assert_eq!(ranked(&Ranking::new(), "Thing", &locations), expected);
+
+    let reversed: Vec<StoreLocation> = locations.into_iter().rev().collect();
+    assert_eq!(ranked(&Ranking::new(), "Thing", &reversed), expected);
+}
+
+#[test]
+fn test_parse_dependencies() {
+    let dependencies = parse_dependencies(r#"
+[package]
+name = "project"
+
+[dependencies]
+serde = "1.0"
+error-chain = { version = "0.11" }
+json = { package = "serde_json", version = "1.0" }
+
+[dev-dependencies]
+tempdir = "0.3"
+
+[target.'cfg(unix)'.dependencies]
+libc = "0.2"
+"#).unwrap();
+
+    let mut found: Vec<String> = dependencies.into_iter().collect();
+    found.sort();
+    assert_eq!(found, vec!["error_chain", "libc", "serde", "serde_json", "tempdir"]);
+}
+
+#[test]
+fn test_store_lookup_is_ranked() {
+    let store = util::memory_store_from_source("crate", "1.0.0", r#"
+pub mod vec {
+    pub struct Vec;
+}
+"#);
+
+    let found: Vec<String> = store.lookup_name("Vec")
+        .into_iter()
+        .map(|loc| loc.mod_path.to_string())
+        .collect();
+    assert_eq!(found, vec!["crate::vec::Vec", "crate::vec"]);
+
+    let found: Vec<String> = store.lookup_name("vec")
+        .into_iter()
+        .map(|loc| loc.mod_path.to_string())
+        .collect();
+    assert_eq!(found, vec!["crate::vec", "crate::vec::Vec"]);
+}
@@ -0,0 +1,133 @@
+use std::sync::Arc;
+
+use oxidoc::backend::{Backend, MemoryBackend};
+use oxidoc::search::SearchFilter;
+use oxidoc::store::Store;
+use oxidoc::type_search::*;
+use util;
+
+const SRC: &str = r#"
+pub fn parse_u32(s: &str) -> Result<u32, ParseError> { unimplemented!() }
+
+pub fn parse_bool(s: &str) -> Result<bool, ParseError> { unimplemented!() }
+
+pub fn first<T>(v: Vec<T>) -> Option<T> { unimplemented!() }
+
+pub fn repeat(s: &str, count: usize) -> String { unimplemented!() }
+
+pub struct Stack;
+
+impl Stack {
+    pub fn len(&self) -> usize { 0 }
+}
+"#;
+
+fn named(name: &str, args: Vec<SigType>) -> SigType {
+    SigType::Named(name.to_string(), args)
+}
+
+fn found_paths(store: &Store, query: &str) -> Vec<String> {
+    found_paths_filtered(store, query, &SearchFilter::new())
+}
+
+fn found_paths_filtered(store: &Store, query: &str, filter: &SearchFilter) -> Vec<String> {
+    store.lookup_signature(query, filter)
+        .unwrap()
+        .into_iter()
+        .map(|(_, loc)| loc.mod_path.to_string())
+        .collect()
+}
+
+fn new_store() -> Store {
+    util::memory_store_from_source("crate", "1.0.0", SRC)
+}
+
+#[test]
+fn test_parse_signature_query() {
+    let query = SignatureQuery::parse("&str -> Result<u32, _>").unwrap();
+    assert_eq!(query.inputs, vec![SigType::Ref(Box::new(named("str", vec![])))]);
+    assert_eq!(query.output, Some(named("Result", vec![named("u32", vec![]), SigType::Any])));
+
+    let query = SignatureQuery::parse("std::vec::Vec<T> -> usize -> Option<T>").unwrap();
+    assert_eq!(query.inputs, vec![named("Vec", vec![SigType::Generic("T".to_string())]),
+                                  named("usize", vec![])]);
+    assert_eq!(query, SignatureQuery::parse("Vec<T>, usize -> Option<T>").unwrap());
+
+    let query = SignatureQuery::parse("-> String").unwrap();
+    assert!(query.inputs.is_empty());
+    assert_eq!(query.output, Some(named("String", vec![])));
+
+    assert!(SignatureQuery::parse("").is_err());
+    assert!(SignatureQuery::parse("Vec<T").is_err());
+    assert!(SignatureQuery::parse("-> A, B").is_err());
+}
+
+#[test]
+fn test_lookup_signature() {
+    let store = new_store();
+
+    assert_eq!(found_paths(&store, "&str -> Result<u32, _>"), vec!["crate::parse_u32"]);
+
+    // `_` and generic parameters match any type.
+    let found = found_paths(&store, "&str -> Result<_, _>");
+    assert_eq!(found.len(), 2);
+    assert!(found.contains(&"crate::parse_bool".to_string()));
+
+    assert_eq!(found_paths(&store, "Vec<T> -> Option<T>"), vec!["crate::first"]);
+    assert_eq!(found_paths(&store, "Vec<u8> -> Option<u8>"), vec!["crate::first"]);
+    assert!(found_paths(&store, "Vec<u8> -> Option<String>").is_empty());
+}
+
+#[test]
+fn test_lookup_signature_ignores_argument_order() {
+    let store = new_store();
+
+    assert_eq!(found_paths(&store, "usize, &str -> String"), vec!["crate::repeat"]);
+    assert_eq!(found_paths(&store, "&str, usize -> String"), vec!["crate::repeat"]);
+}
+
+#[test]
+fn test_lookup_signature_ranks_closest_first() {
+    let store = new_store();
+
+    // Every function taking a `&str` matches, but those taking nothing else rank first.
+    let found = found_paths(&store, "&str");
+    assert_eq!(found.len(), 3);
+    assert_eq!(found[2], "crate::repeat");
+
+    // `self` stands for the implementing type.
+    let found = found_paths(&store, "Stack -> usize");
+    assert_eq!(found, vec!["crate::Stack::len"]);
+}
+
+#[test]
+fn test_lookup_signature_in_older_versions() {
+    let mut store = new_store();
+    util::add_source(&mut store, "crate", "2.0.0", "pub fn parse_u64(s: &str) -> u64 { 0 }");
+
+    assert_eq!(found_paths(&store, "&str -> u64"), vec!["crate::parse_u64"]);
+    assert!(found_paths(&store, "&str -> Result<u32, _>").is_empty());
+
+    let mut filter = SearchFilter::new();
+    filter.version = Some("1".to_string());
+    assert_eq!(found_paths_filtered(&store, "&str -> Result<u32, _>", &filter),
+               vec!["crate::parse_u32"]);
+    assert!(found_paths_filtered(&store, "&str -> u64", &filter).is_empty());
+
+    filter.krate = Some("other".to_string());
+    assert!(found_paths_filtered(&store, "&str -> Result<u32, _>", &filter).is_empty());
+}
+
+#[test]
+fn test_lookup_signature_requires_index() {
+    let backend: Arc<Backend> = Arc::new(MemoryBackend::new());
+    let mut store = Store::with_backend(backend.clone());
+    util::add_source(&mut store, "crate", "1.0.0", SRC);
+    store.save().unwrap();
+
+    let mut store = Store::load_from(backend).unwrap();
+    assert!(store.lookup_signature("-> String", &SearchFilter::new()).is_err());
+
+    store.load_signature_index().unwrap();
+    assert_eq!(found_paths(&store, "usize, &str -> String"), vec!["crate::repeat"]);
+}
@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use oxidoc::backend::{Backend, MemoryBackend};
+use oxidoc::search::SearchFilter;
+use oxidoc::store::Store;
+use oxidoc::text_search::*;
+use util;
+
+const SRC: &str = r#"
+/// Percent-encodes a URL so it can be sent in a request.
+pub fn percent_encode(url: &str) -> String { url.to_string() }
+
+/// Parses a URL.
+///
+/// The URL is checked and split into its parts.
+pub fn parse_url(url: &str) {}
+
+/// Encodes bytes as base64.
+pub fn base64(bytes: &[u8]) {}
+
+/// Percent-encoding internals.
+fn encode_internal() {}
+"#;
+
+fn found_paths(store: &Store, query: &str) -> Vec<String> {
+    found_paths_filtered(store, query, &SearchFilter::new())
+}
+
+fn found_paths_filtered(store: &Store, query: &str, filter: &SearchFilter) -> Vec<String> {
+    store.search_text(query, filter)
+        .unwrap()
+        .into_iter()
+        .map(|(_, loc)| loc.mod_path.to_string())
+        .collect()
+}
+
+#[test]
+fn test_stem_word_forms() {
+    assert_eq!(stem("encodes"), stem("encode"));
+    assert_eq!(stem("encoded"), stem("encode"));
+    assert_eq!(stem("encoding"), stem("encode"));
+    assert_eq!(stem("running"), stem("run"));
+    assert_eq!(stem("libraries"), stem("library"));
+    assert_eq!(stem("addresses"), "address");
+    assert_eq!(stem("string"), "string");
+}
+
+#[test]
+fn test_tokenize_removes_stopwords() {
+    assert_eq!(tokenize("The function that percent-encodes a URL"),
+               vec!["function", "percent", "encod", "url"]);
+}
+
+#[test]
+fn test_search_text_ranks_relevant_documents_first() {
+    let backend: Arc<Backend> = Arc::new(MemoryBackend::new());
+    let mut store = Store::with_backend(backend.clone());
+    util::add_source(&mut store, "crate", "1.0.0", SRC);
+    store.save().unwrap();
+
+    let mut store = Store::load_from(backend).unwrap();
+    store.load_text_index().unwrap();
+
+    let found = found_paths(&store, "the function that percent-encodes a URL");
+    assert_eq!(found[0], "crate::percent_encode");
+    assert!(found.contains(&"crate::parse_url".to_string()));
+    assert!(!found.contains(&"crate::encode_internal".to_string()));
+
+    assert_eq!(found_paths(&store, "base64 encoding"),
+               vec!["crate::base64", "crate::percent_encode"]);
+    assert!(found_paths(&store, "the").is_empty());
+}
+
+#[test]
+fn test_search_text_in_older_versions() {
+    let backend: Arc<Backend> = Arc::new(MemoryBackend::new());
+    let mut store = Store::with_backend(backend.clone());
+    util::add_source(&mut store, "crate", "1.0.0", SRC);
+    util::add_source(&mut store, "crate", "2.0.0", "/// Encodes bytes as hex.\npub fn hex() {}");
+    store.save().unwrap();
+
+    let mut store = Store::load_from(backend).unwrap();
+    store.load_text_index().unwrap();
+
+    assert!(found_paths(&store, "base64").is_empty());
+
+    let mut filter = SearchFilter::new();
+    filter.version = Some("1.0.0".to_string());
+    assert_eq!(found_paths_filtered(&store, "base64", &filter), vec!["crate::base64"]);
+    assert!(found_paths_filtered(&store, "hex", &filter).is_empty());
+}
+
+#[test]
+fn test_search_text_requires_index() {
+    let store = Store::new();
+    assert!(store.search_text("url", &SearchFilter::new()).is_err());
+}
+
+#[test]
+fn test_snippet_highlights_matches() {
+    let highlight = |word: &str| format!("*{}*", word);
+    let text = "Returns the number of elements. Percent-encodes the string with the given set, \
+                which is useful for URLs.";
+
+    assert_eq!(snippet(text, "encoding urls", 6, &highlight),
+               "... of elements. *Percent-encodes* the string with ...");
+    assert_eq!(snippet("URL parsing", "url", 10, &highlight), "*URL* parsing");
+}
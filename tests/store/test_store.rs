@@ -1,35 +1,82 @@
-use conversion::Documentation;
-use std::collections::{HashMap, HashSet};
-use std::fmt;
-use std::fs::{File, OpenOptions};
-use std::io::{Read, Write};
-use std::path::{Path, PathBuf};
+use std::cmp::Ordering;
+use std::fs::{self, File};
+use std::io::Write;
+use std::process::{self, Command};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
 
-use bincode::{self, Infinite};
-use serde::de::DeserializeOwned;
-use serde::ser::Serialize;
-use strsim::levenshtein;
+use oxidoc::backend::{Backend, FsBackend, MemoryBackend};
+use oxidoc::bundle::Bundle;
+use oxidoc::conversion::DocType;
+use oxidoc::document::{CrateInfo, ModPath};
+use oxidoc::errors::*;
+use oxidoc::paths;
+use oxidoc::store::*;
+use util;
 
-use conversion::DocType;
-use document::CrateInfo;
-use document::ModPath;
-use paths;
-use std::env;
+fn memory_backend() -> Arc<Backend> {
+    Arc::new(MemoryBackend::new())
+}
 
 #[test]
 fn test_read_write_bincode() {
     let string = "Test.".to_string();
-    let mut dir = env::temp_dir();
-    dir.push("test.txt");
+    let dir = util::TempDir::new("bincode");
+    let path = dir.path().join("test.txt");
 
-    serialize_object(&string, &dir).expect("Write failed");
-    let result: String = deserialize_object(&dir).expect("Read failed");
+    serialize_object(&string, &path).expect("Write failed");
+    let result: String = deserialize_object(&path).expect("Read failed");
 
     assert_eq!(string, result);
 }
 
 #[test]
-fn test_store_loc_to_path() {
+fn test_overwrite_with_shorter_data() {
+    let dir = util::TempDir::new("overwrite");
+    let path = dir.path().join("test_overwrite.odoc");
+
+    serialize_object(&"A much longer string than the next one.".to_string(), &path).unwrap();
+    serialize_object(&"Short.".to_string(), &path).unwrap();
+    let result: String = deserialize_object(&path).expect("Read failed");
+
+    assert_eq!(result, "Short.");
+}
+
+#[test]
+fn test_read_unknown_format() {
+    let dir = util::TempDir::new("unknown_format");
+    let path = dir.path().join("test_unknown_format.odoc");
+
+    let mut file = File::create(&path).unwrap();
+    file.write_all(b"not an oxidoc file").unwrap();
+
+    match deserialize_object::<String, _>(&path) {
+        Err(Error(ErrorKind::UnknownFormat(..), _)) => (),
+        other => panic!("Expected an unknown format error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_read_incompatible_format() {
+    let dir = util::TempDir::new("incompatible_format");
+    let path = dir.path().join("test_incompatible_format.odoc");
+
+    let mut file = File::create(&path).unwrap();
+    file.write_all(b"OXIDOC").unwrap();
+    file.write_all(&[0xff, 0xff, 0, 0]).unwrap();
+
+    match deserialize_object::<String, _>(&path) {
+        Err(Error(ErrorKind::IncompatibleFormat(_, found, expected), _)) => {
+            assert_eq!(found, 0xffff);
+            assert_eq!(expected, FORMAT_VERSION);
+        },
+        other => panic!("Expected an incompatible format error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_store_loc_to_archive_name() {
     let loc = StoreLocation {
         name: "Test".to_string(),
         crate_info: CrateInfo {
@@ -39,21 +86,330 @@ fn test_store_loc_to_path() {
         },
         mod_path: ModPath::from("crate::thing".to_string()),
         doc_type: DocType::Struct,
+        is_private: false,
+        is_unsafe: false,
+        is_const_fn: false,
+        is_method: false,
+        offset: 0,
+        length: 0,
+        archive_id: 0,
+        summary: String::new(),
+        signature: String::new(),
+        backend: None,
     };
 
-    let path = loc.to_filepath().display().to_string();
-    assert!(path.contains("test-0.1.0"), "{}", path);
-    assert!(path.contains("crate"), "{}", path);
-    assert!(path.contains("thing"), "{}", path);
-    assert!(path.contains("sdesc-Test.odoc"), "{}", path);
+    assert_eq!(loc.archive_name().unwrap(), "test@0.1.0.odoc");
+}
+
+#[test]
+fn test_read_archive_records() {
+    let src = r#"
+pub struct Thing;
+pub mod stuff {
+    pub fn thing() {}
+}
+"#;
+    let source_to_docs = || util::source_to_crate_docs("archive_test", "0.1.0", src);
+
+    let docset = Docset::from_documents(&memory_backend(), source_to_docs()).unwrap();
+    assert_eq!(docset.find("stuff::thing").len(), 1);
+
+    for expected in source_to_docs() {
+        let location = &docset.documents[&expected.to_store_location().key()];
+        assert_eq!(read_archive_record(location).unwrap(), expected);
+    }
+}
+
+#[test]
+fn test_items_differing_by_case_or_kind() {
+    let docs = util::source_to_crate_docs("key_test", "0.1.0", r#"
+pub struct Foo;
+pub fn foo() {}
+pub mod bar {}
+pub fn bar() {}
+"#);
+
+    let docset = Docset::from_documents(&memory_backend(), docs).unwrap();
+
+    let mut foo_types: Vec<DocType> = docset.find("foo").into_iter().map(|l| l.doc_type.clone()).collect();
+    let mut bar_types: Vec<DocType> = docset.find("BAR").into_iter().map(|l| l.doc_type.clone()).collect();
+    foo_types.sort_by_key(|t| format!("{:?}", t));
+    bar_types.sort_by_key(|t| format!("{:?}", t));
+
+    assert_eq!(foo_types, vec![DocType::Function, DocType::Struct]);
+    assert_eq!(bar_types, vec![DocType::Function, DocType::Module]);
+}
+
+#[test]
+fn test_save_and_load_memory_store() {
+    let backend = memory_backend();
+    let source_to_docs = || util::source_to_crate_docs("crate", "0.1.0", "pub struct Thing;");
+
+    let mut store = Store::with_backend(backend.clone());
+    store.add_documents(util::get_crate_info("crate", "0.1.0"), source_to_docs()).unwrap();
+    store.save().unwrap();
+
+    let loaded = Store::load_from(backend.clone()).unwrap();
+    let found = loaded.lookup_name("thing");
+    assert_eq!(found.len(), 1);
+    let expected = source_to_docs().into_iter()
+        .find(|doc| doc.to_store_location().key() == found[0].key())
+        .unwrap();
+    assert_eq!(read_archive_record(found[0]).unwrap(), expected);
+    assert!(loaded.check_integrity().unwrap().is_ok());
+}
+
+#[test]
+fn test_repair_only_removes_oxidoc_files() {
+    let dir = util::TempDir::new("repair");
+    let root = dir.path().to_path_buf();
+    fs::create_dir_all(root.join("serde-1.0.0").join("serde")).unwrap();
+    fs::create_dir_all(root.join("my-project")).unwrap();
+
+    let backend: Arc<Backend> = Arc::new(FsBackend::new(root.clone()));
+    let mut store = Store::with_backend(backend.clone());
+    util::add_source(&mut store, "crate", "1.0.0", "pub struct Thing;");
+    store.save().unwrap();
+
+    // The temporary file of a process which has exited is stale.
+    let mut child = Command::new("true").spawn().unwrap();
+    let dead_pid = child.id();
+    child.wait().unwrap();
+    let stale_temp_file = format!(".store.{}.tmp", dead_pid);
+    let running_temp_file = format!(".store.{}.tmp", process::id());
+    let names = ["notes.txt", "other@1.0.0.odoc", stale_temp_file.as_str(), running_temp_file.as_str()];
+    for name in names.iter() {
+        File::create(root.join(name)).unwrap();
+    }
+    File::create(root.join("serde-1.0.0").join("serde").join("sdesc-Serialize.odoc")).unwrap();
+
+    // Directories holding anything but documentation are left alone.
+    fs::create_dir_all(root.join("regex-0.2.0")).unwrap();
+    File::create(root.join("regex-0.2.0").join("notes.txt")).unwrap();
+    File::create(root.join("regex-0.2.0").join("sdesc-Regex.odoc")).unwrap();
+
+    let report = store.check_integrity().unwrap();
+    let mut orphaned = report.orphaned.clone();
+    orphaned.sort();
+    assert_eq!(orphaned, vec![stale_temp_file.as_str(), "other@1.0.0.odoc"]);
+    let mut legacy_dirs = report.legacy_dirs.clone();
+    legacy_dirs.sort();
+    assert_eq!(legacy_dirs, vec!["regex-0.2.0", "serde-1.0.0"]);
+
+    let skipped = store.repair(&report).unwrap();
+    assert_eq!(skipped, vec!["regex-0.2.0"]);
+    assert!(store.check_integrity().unwrap().is_ok());
+    assert!(!root.join("serde-1.0.0").exists());
+    assert!(root.join("notes.txt").exists());
+    assert!(root.join("my-project").exists());
+    assert!(root.join(&running_temp_file).exists());
+    assert!(root.join("regex-0.2.0").join("notes.txt").exists());
+    assert!(root.join("regex-0.2.0").join("sdesc-Regex.odoc").exists());
+}
+
+#[test]
+fn test_clear_keeps_lock_and_foreign_files() {
+    let dir = util::TempDir::new("clear");
+    let root = dir.path().to_path_buf();
+    fs::create_dir_all(root.join("serde-1.0.0")).unwrap();
+    fs::create_dir_all(root.join("my-project")).unwrap();
+
+    let _lock = StoreLock::acquire_in(&root).unwrap();
+
+    let backend: Arc<Backend> = Arc::new(FsBackend::new(root.clone()));
+    let mut store = Store::with_backend(backend.clone());
+    util::add_source(&mut store, "crate", "1.0.0", "pub struct Thing;");
+    store.save().unwrap();
+    File::create(root.join("notes.txt")).unwrap();
+    File::create(root.join("other@1.0.0.odoc")).unwrap();
+
+    store.clear().unwrap();
+    assert!(store.crate_names().is_empty());
+
+    let mut remaining = backend.list().unwrap();
+    remaining.sort();
+    assert_eq!(remaining, vec!["my-project", "notes.txt", "serde-1.0.0", paths::STORE_LOCK_FILENAME]);
+    assert!(Store::load_from(backend).unwrap().crate_names().is_empty());
+}
+
+#[test]
+fn test_store_lock_is_exclusive() {
+    let dir = util::TempDir::new("lock");
+    let root = dir.path().to_path_buf();
+
+    let lock = StoreLock::acquire_in(&root).unwrap();
+
+    let (sender, receiver) = mpsc::channel();
+    let thread_root = root.clone();
+    let waiter = thread::spawn(move || {
+        let _lock = StoreLock::acquire_in(&thread_root).unwrap();
+        sender.send(()).unwrap();
+    });
+
+    // The other thread has to wait until the lock is released.
+    assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+    drop(lock);
+    assert!(receiver.recv_timeout(Duration::from_secs(10)).is_ok());
+
+    waiter.join().unwrap();
+    assert!(root.join(paths::STORE_LOCK_FILENAME).exists());
+}
+
+#[test]
+fn test_export_and_import_bundle() {
+    let src = "pub mod stuff { pub struct Thing; }";
+    let source = util::memory_store_from_source("crate", "0.2.0", src);
+
+    let bundle = source.export_bundle("crate", None).unwrap();
+    assert_eq!(bundle.crate_info.version, "0.2.0");
+    let data = bundle.to_bytes().unwrap();
+
+    let mut target = Store::with_backend(memory_backend());
+    let imported = target.import_bundle(Bundle::from_bytes(&data, "bundle").unwrap()).unwrap();
+    assert_eq!(imported.name, "crate");
+
+    let found = target.lookup_name("stuff::thing");
+    assert_eq!(found.len(), 1);
+    let expected = util::source_to_crate_docs("crate", "0.2.0", src).into_iter()
+        .find(|doc| doc.to_store_location().key() == found[0].key())
+        .unwrap();
+    assert_eq!(read_archive_record(found[0]).unwrap(), expected);
+    assert!(target.check_integrity().unwrap().is_ok());
+
+    assert!(Bundle::from_bytes(b"not a bundle", "bundle").is_err());
+    assert!(source.export_bundle("crate", Some("1.0")).is_err());
+}
+
+#[test]
+fn test_list_crates_and_items() {
+    let mut store = Store::with_backend(memory_backend());
+    for &(version, src) in [("0.1.0", "pub struct Old;"),
+                            ("0.2.0", "pub struct Thing; pub mod stuff { pub fn a() {} pub struct B; }")].iter() {
+        util::add_source(&mut store, "crate", version, src);
+    }
+
+    assert_eq!(store.crate_names(), vec!["crate"]);
+    assert_eq!(store.crate_versions("crate"), vec!["0.2.0", "0.1.0"]);
+    assert!(store.crate_versions("missing").is_empty());
+
+    let (version, docset) = store.docset("crate", None).unwrap();
+    assert_eq!(version, "0.2.0");
+    let counts = docset.count_by_type();
+    assert!(counts.contains(&(DocType::Struct, 2)), "{:?}", counts);
+    assert!(counts.contains(&(DocType::Function, 1)), "{:?}", counts);
+
+    let children: Vec<String> = store.children(&ModPath::from("crate::stuff".to_string()), None, false)
+        .into_iter()
+        .map(|loc| loc.name.clone())
+        .collect();
+    assert_eq!(children, vec!["a", "B"]);
+
+    let old: Vec<String> = store.children(&ModPath::from("crate".to_string()), Some("0.1.0"), false)
+        .into_iter()
+        .map(|loc| loc.name.clone())
+        .collect();
+    assert_eq!(old, vec!["Old"]);
+
+    let tree: Vec<(usize, String)> = store.tree(&ModPath::from("crate".to_string()), None, false)
+        .into_iter()
+        .map(|(depth, loc)| (depth, loc.name.clone()))
+        .collect();
+    assert_eq!(tree, vec![(0, "stuff".to_string()),
+                          (1, "a".to_string()),
+                          (1, "B".to_string()),
+                          (0, "Thing".to_string())]);
+}
+
+#[test]
+fn test_summaries_in_index() {
+    let store = util::memory_store_from_source("crate", "1.0.0", r#"
+/// Does a thing
+/// quickly.
+///
+/// More details.
+pub unsafe fn thing(x: u32) -> u32 { x }
+
+pub struct Undocumented;
+"#);
+
+    let thing = store.lookup_path(&ModPath::from("crate::thing".to_string())).unwrap();
+    assert_eq!(thing.summary, "Does a thing quickly.");
+    assert!(thing.signature.starts_with("unsafe fn thing"), "{}", thing.signature);
+    assert!(thing.signature.contains("u32"), "{}", thing.signature);
+    assert_eq!(thing.short_summary(10), "Does a ...");
+
+    let undocumented = store.lookup_path(&ModPath::from("crate::Undocumented".to_string())).unwrap();
+    assert_eq!(undocumented.summary, "");
+    assert_eq!(undocumented.signature, "struct Undocumented");
+}
+
+#[test]
+fn test_encode_file_name() {
+    assert_eq!(paths::encode_file_name("serde_json").unwrap(), "serde_json");
+    assert_eq!(paths::encode_file_name("1.0.0-beta.2+build").unwrap(), "1.0.0-beta.2+build");
+    assert_ne!(paths::encode_file_name("Foo").unwrap(), paths::encode_file_name("foo").unwrap());
+    assert_eq!(paths::encode_file_name("a/b").unwrap(), "a%2Fb");
+    assert_eq!(paths::encode_file_name("..").unwrap(), "%2E.");
+    assert!(paths::encode_file_name("").is_err());
+}
+
+#[test]
+fn test_archive_crate_version() {
+    let crate_info = CrateInfo {
+        name: "Serde_Derive".to_string(),
+        version: "1.0.0-beta.2+build".to_string(),
+        lib_path: None,
+    };
+    let archive_name = paths::crate_archive_name(&crate_info).unwrap();
+    assert_eq!(paths::archive_crate_version(&archive_name),
+               Some("Serde_Derive@1.0.0-beta.2+build".to_string()));
+
+    assert_eq!(paths::archive_crate_version("store"), None);
+    assert_eq!(paths::archive_crate_version("bad%4@1.0.0.odoc"), None);
 }
 
 #[test]
 fn test_compare_version_numbers() {
-    let assert_second_newer = |a, b| assert!(version_number_hash(a) < version_number_hash(b),
+    let assert_second_newer = |a, b| assert!(compare_versions(a, b) == Ordering::Less,
                                              "{} {}", a, b);
     assert_second_newer("0.1.0", "0.2.0");
     assert_second_newer("0.1.0", "1.0.0");
     assert_second_newer("0.1.0", "1.0.1");
     assert_second_newer("0.0.1", "0.1.0");
 }
+
+#[test]
+fn test_compare_prerelease_versions() {
+    let assert_second_newer = |a, b| assert!(compare_versions(a, b) == Ordering::Less,
+                                             "{} {}", a, b);
+    assert_second_newer("1.0.0-alpha", "1.0.0-alpha.1");
+    assert_second_newer("1.0.0-alpha.1", "1.0.0-beta");
+    assert_second_newer("1.0.0-beta.2", "1.0.0-beta.11");
+    assert_second_newer("1.0.0-rc.1", "1.0.0");
+    assert_second_newer("0.9.9", "1.0.0-beta.2");
+}
+
+#[test]
+fn test_compare_large_version_components() {
+    let assert_second_newer = |a, b| assert!(compare_versions(a, b) == Ordering::Less,
+                                             "{} {}", a, b);
+    assert_second_newer("0.3.0", "0.300.0");
+    assert_second_newer("0.300.0", "1.0.0");
+    assert_second_newer("0.0.255", "0.0.256");
+    assert_second_newer("1.99999999999999999999.0", "1.100000000000000000000.0");
+}
+
+#[test]
+fn test_compare_build_metadata() {
+    assert!(compare_versions("1.0.0+build.1", "1.0.0-rc.1") == Ordering::Greater);
+    assert!(compare_versions("1.0.0+build.1", "1.0.1") == Ordering::Less);
+}
+
+#[test]
+fn test_compare_invalid_versions() {
+    assert!(compare_versions("1.0", "0.1.0") == Ordering::Greater);
+    assert!(compare_versions("0.1.0", "1.0") == Ordering::Less);
+    assert!(compare_versions("1.0", "1.1") == Ordering::Less);
+    assert!(compare_versions("1.0", "1.0.0") == Ordering::Less);
+    assert!(compare_versions("2", "1.99999999999999999999.0") == Ordering::Greater);
+}
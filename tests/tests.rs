@@ -15,6 +15,8 @@ extern crate oxidoc;
 #[cfg(unix)]
 extern crate pager;
 
+mod cli;
 mod conversion;
 mod search;
+mod store;
 mod util;
@@ -1,6 +1,15 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+
+use oxidoc::backend::MemoryBackend;
 use oxidoc::conversion::Documentation;
 use oxidoc::document::{CrateInfo, ModPath};
-use oxidoc::generation;
+use oxidoc::generation::{self, GenerationOptions};
+use oxidoc::store::Store;
 
 use syntax::codemap::FilePathMapping;
 use syntax::parse::{self, ParseSess};
@@ -28,17 +37,75 @@ fn parse_crate_from_source(docs_string: String) -> ast::Crate {
 }
 
 pub fn source_to_docs(docs_str: &str) -> Vec<Documentation> {
+    source_to_docs_with_options(docs_str, &GenerationOptions::default())
+}
+
+pub fn source_to_docs_with_options(docs_str: &str, options: &GenerationOptions) -> Vec<Documentation> {
+    generate_docs(get_crate_info("crate", "1.0.0"), docs_str, options)
+}
+
+/// Generates the documentation of `docs_str` as the crate version `name@version`.
+pub fn source_to_crate_docs(name: &str, version: &str, docs_str: &str) -> Vec<Documentation> {
+    generate_docs(get_crate_info(name, version), docs_str, &GenerationOptions::default())
+}
+
+fn generate_docs(crate_info: CrateInfo, docs_str: &str, options: &GenerationOptions) -> Vec<Documentation> {
     let krate = parse_crate_from_source(docs_str.to_string());
 
-    let crate_info = get_crate_info("crate", "1.0.0");
-    let l = generation::generate_crate_docs(krate, crate_info).unwrap();
+    let l = generation::generate_crate_docs(krate, crate_info, options).unwrap();
     for i in l.iter() {
         debug!("{}", i.mod_path);
     }
     l
 }
 
+/// Adds the documentation of `docs_str` to `store` as the crate version `name@version`.
+pub fn add_source(store: &mut Store, name: &str, version: &str, docs_str: &str) {
+    let docs = source_to_crate_docs(name, version, docs_str);
+    store.add_documents(get_crate_info(name, version), docs).unwrap();
+}
+
+/// Creates a store in memory holding the documentation of `docs_str` as the crate version
+/// `name@version`.
+pub fn memory_store_from_source(name: &str, version: &str, docs_str: &str) -> Store {
+    let mut store = Store::with_backend(Arc::new(MemoryBackend::new()));
+    add_source(&mut store, name, version, docs_str);
+    store
+}
+
+pub fn find_doc<'a>(docs: &'a Vec<Documentation>, path: &str) -> &'a Documentation {
+    docs.iter()
+        .find(|doc| doc.mod_path.to_string() == path)
+        .expect(&format!("No documentation found for {}", path))
+}
+
 pub fn print_paths(paths: &Vec<ModPath>) -> String {
     let strings: Vec<String> = paths.iter().cloned().map(|p| p.to_string()).collect();
     strings.join("\n")
 }
+
+/// A directory for the files of a single test, removed again when dropped. Its name includes the
+/// process ID and a counter, so tests running at the same time never share it.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> TempDir {
+        static COUNTER: AtomicUsize = ATOMIC_USIZE_INIT;
+
+        let id = COUNTER.fetch_add(1, Ordering::SeqCst);
+        let path = env::temp_dir().join(format!("oxidoc_test_{}_{}_{}", name, process::id(), id));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}